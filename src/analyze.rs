@@ -0,0 +1,451 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::{
+    args::{Args, ArgsError, Backend, ClockMode, LogFormat, SchedulerKind},
+    clock::millis,
+    codexion::{Codexion, SimulationOutcome},
+    topology::Topology,
+};
+
+/// What the arguments need to avoid a burnout, in theory and with the schedulers
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    pub number_of_coders: u32,
+    pub time_to_burnout: Duration,
+    /// means were used for durations drawn from a distribution
+    pub random_durations: bool,
+    /// gaps between compiles no scheduler can avoid
+    pub bounds: Vec<Bound>,
+    pub schedulers: Vec<SchedulerAnalysis>,
+}
+
+/// Some coder goes at least `gap` without compiling, whatever the scheduler
+#[derive(Clone, Debug)]
+pub struct Bound {
+    pub name: &'static str,
+    pub gap: Duration,
+    pub reasoning: String,
+}
+
+/// How a scheduler does with these arguments, on the virtual clock
+#[derive(Clone, Debug)]
+pub struct SchedulerAnalysis {
+    pub scheduler: SchedulerKind,
+    pub result: Measurement,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Measurement {
+    /// every coder did his compiles, none went longer than `longest_gap` without compiling
+    Completed { longest_gap: Duration },
+    /// a coder waited longer than a burnout could ever be
+    Starved { coder_id: u32 },
+    /// the backend can't run on the virtual clock
+    NotMeasured,
+    /// a signal stopped the run before it was over
+    Interrupted,
+}
+
+/// Long enough that nobody burns out while the schedule is measured
+const NO_BURNOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+impl Analysis {
+    /// Longest gap some coder has to go through, whatever the scheduler
+    pub fn minimum_gap(&self) -> Duration {
+        self.bounds
+            .iter()
+            .map(|bound| bound.gap)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Whether some scheduler could, in theory, keep everyone from burning out
+    pub fn is_feasible(&self) -> bool {
+        self.time_to_burnout > self.minimum_gap()
+    }
+}
+
+impl SchedulerAnalysis {
+    /// Whether it burns out with `time_to_burnout`, `None` if it wasn't measured
+    pub fn burns_out(&self, time_to_burnout: Duration) -> Option<bool> {
+        match self.result {
+            Measurement::Completed { longest_gap } => Some(longest_gap >= time_to_burnout),
+            Measurement::Starved { .. } => Some(true),
+            Measurement::NotMeasured | Measurement::Interrupted => None,
+        }
+    }
+}
+
+/// Bound the gaps between compiles and measure FIFO and EDF, plus the
+/// scheduler of the arguments if it's another one
+pub fn analyze(args: &Args) -> Analysis {
+    let mut schedulers = vec![SchedulerKind::Fifo, SchedulerKind::Edf];
+    if !matches!(args.scheduler, SchedulerKind::Fifo | SchedulerKind::Edf) {
+        schedulers.push(args.scheduler);
+    }
+
+    Analysis {
+        number_of_coders: args.number_of_coders,
+        time_to_burnout: args.time_to_burnout,
+        random_durations: args.random_durations(),
+        bounds: bounds(args),
+        schedulers: schedulers
+            .into_iter()
+            .map(|scheduler| SchedulerAnalysis {
+                scheduler,
+                result: measure(args, scheduler),
+            })
+            .collect(),
+    }
+}
+
+fn bounds(args: &Args) -> Vec<Bound> {
+    let graph = args.graph();
+    let n = args.number_of_coders as usize;
+    let profiles: Vec<_> = (1..=args.number_of_coders)
+        .map(|coder_id| args.coder_profile(coder_id))
+        .collect();
+    let compile = |idx: usize| profiles[idx].time_to_compile.mean();
+    let cooldown = |dongle_idx: usize| args.cooldown_of(dongle_idx as u32 + 1).mean();
+    // the steady state bounds only hold for coders who compile again
+    let compiles_again = |idx: usize| profiles[idx].number_of_compiles_required > 1;
+
+    let mut sharers = vec![Vec::new(); graph.number_of_dongles as usize];
+    for (idx, dongles) in graph.coders.iter().enumerate() {
+        let mut dongles = dongles.clone();
+        dongles.dedup();
+        for dongle_idx in dongles {
+            sharers[dongle_idx].push(idx);
+        }
+    }
+
+    let mut bounds = Vec::new();
+
+    // a coder compiles, debugs and refactors, and his dongles cool down
+    if let Some((idx, gap)) = (0..n)
+        .rev()
+        .filter(|&idx| compiles_again(idx))
+        .map(|idx| {
+            let profile = &profiles[idx];
+            let cooled_down = graph.coders[idx]
+                .iter()
+                .map(|&dongle_idx| cooldown(dongle_idx))
+                .max()
+                .unwrap_or_default();
+            let busy = profile.time_to_debug.mean() + profile.time_to_refactor.mean();
+            (idx, compile(idx) + busy.max(cooled_down))
+        })
+        .max_by_key(|&(_, gap)| gap)
+    {
+        bounds.push(Bound {
+            name: "own cycle",
+            gap,
+            reasoning: format!(
+                "coder {} compiles for {:.1} ms, then debugs and refactors, or waits for his dongles to cool down, before he compiles again",
+                idx + 1,
+                millis(compile(idx))
+            ),
+        });
+    }
+
+    // coders sharing a dongle compile one after the other
+    // the lowest id wins a tie, so they're looked at from the last
+    let first_round = sharers
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, coders)| !coders.is_empty())
+        .map(|(dongle_idx, coders)| {
+            let compiles: Duration = coders.iter().map(|&idx| compile(idx)).sum();
+            let cooldowns = cooldown(dongle_idx) * (coders.len() as u32 - 1);
+            (dongle_idx, coders.len(), compiles + cooldowns)
+        })
+        .max_by_key(|&(_, _, gap)| gap);
+    if let Some((dongle_idx, count, gap)) = first_round {
+        bounds.push(Bound {
+            name: "first compiles",
+            gap,
+            reasoning: format!(
+                "dongle {} is needed by {count} coder(s), the last one to get it first compiles after the others and {} cooldown(s)",
+                dongle_idx + 1,
+                count - 1
+            ),
+        });
+    }
+    let sharing = sharers
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, coders)| coders.iter().any(|&idx| compiles_again(idx)))
+        .map(|(dongle_idx, coders)| {
+            let gap: Duration = coders
+                .iter()
+                .map(|&idx| compile(idx) + cooldown(dongle_idx))
+                .sum();
+            (dongle_idx, coders.len(), gap)
+        })
+        .max_by_key(|&(_, _, gap)| gap);
+    if let Some((dongle_idx, count, gap)) = sharing
+        && count > 1
+    {
+        bounds.push(Bound {
+            name: "dongle sharing",
+            gap,
+            reasoning: format!(
+                "dongle {} is needed by {count} coders, each holds it to compile and it cools down after each one, before the first can have it again",
+                dongle_idx + 1
+            ),
+        });
+    }
+
+    // on a ring, neighbours can't compile at the same time
+    if args.topology == Topology::Ring && args.coder_dongles.is_empty() && n >= 3 {
+        let at_once = n / 2;
+        let min_compile = (0..n).map(compile).min().unwrap_or_default();
+        let min_cooldown = (0..n).map(cooldown).min().unwrap_or_default();
+
+        if n % 2 == 1 {
+            // a coder compiles after a neighbour who compiles after his other neighbour
+            bounds.push(Bound {
+                name: "odd ring, first compiles",
+                gap: min_compile * 3 + min_cooldown * 2,
+                reasoning: format!(
+                    "{n} coders can't be split in two groups of non-neighbours, so some coder first compiles after two others, one after the other"
+                ),
+            });
+        }
+        if (0..n).all(compiles_again) {
+            let compiles: Duration = (0..n).map(|idx| compile(idx) + min_cooldown).sum();
+            bounds.push(Bound {
+                name: if n % 2 == 1 { "odd ring" } else { "even ring" },
+                gap: compiles / at_once as u32,
+                reasoning: format!(
+                    "with {n} coders at most {at_once} compile at once, so every coder compiling once, dongle cooldown included, takes {:.2} rounds",
+                    n as f64 / at_once as f64
+                ),
+            });
+        }
+    }
+
+    bounds
+}
+
+/// Run the arguments on the virtual clock, with nobody ever burning out
+fn measure(args: &Args, scheduler: SchedulerKind) -> Measurement {
+    if args.backend == Backend::ChandyMisra {
+        return Measurement::NotMeasured;
+    }
+
+    let mut args = args.clone();
+    args.scheduler = scheduler;
+    args.time_to_burnout = NO_BURNOUT;
+    args.clock = ClockMode::Virtual;
+    args.log_format = LogFormat::Quiet;
+    args.stats = false;
+    args.watchdog = false;
+    args.interactive = false;
+    args.interruption_summary = false;
+    args.post_mortem = None;
+    args.trace_out = None;
+    args.record = None;
+    // a recording would measure its own order, not the scheduler's
+    args.replay = None;
+
+    let codexion = Codexion::new(args);
+    match codexion.start() {
+        SimulationOutcome::Completed => Measurement::Completed {
+            longest_gap: codexion
+                .statistics()
+                .coders
+                .iter()
+                .map(|coder| coder.longest_gap)
+                .max()
+                .unwrap_or_default(),
+        },
+        SimulationOutcome::BurnedOut { coder_id, .. } => Measurement::Starved { coder_id },
+        SimulationOutcome::Interrupted { .. } => Measurement::Interrupted,
+    }
+}
+
+/// Analyze the arguments and print the report, returns whether a burnout can be avoided
+pub fn run(args: &[String]) -> Result<bool, ArgsError> {
+    let mut tokens = vec!["analyze".to_string()];
+    tokens.extend(args.iter().cloned());
+    let args = Args::try_from(tokens)?;
+
+    let analysis = analyze(&args);
+    println!("{analysis}");
+    Ok(analysis.is_feasible())
+}
+
+impl Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "=== Analysis: {} coders, time_to_burnout {:.1} ms ===",
+            self.number_of_coders,
+            millis(self.time_to_burnout)
+        )?;
+        if self.random_durations {
+            writeln!(
+                f,
+                "durations are random: the bounds use their means, the runs the draws of the seed"
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "gaps between compiles no scheduler can avoid:")?;
+        for bound in &self.bounds {
+            writeln!(
+                f,
+                "{:>26} {:>9.1} ms  {}",
+                bound.name,
+                millis(bound.gap),
+                bound.reasoning
+            )?;
+        }
+        let minimum_gap = self.minimum_gap();
+        writeln!(
+            f,
+            "minimum safe time_to_burnout: more than {:.1} ms",
+            millis(minimum_gap)
+        )?;
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "longest gap between compiles on the virtual clock, without burnout:"
+        )?;
+        for scheduler in &self.schedulers {
+            let name = format!("{:?}", scheduler.scheduler).to_lowercase();
+            let verdict = match scheduler.result {
+                Measurement::Completed { longest_gap } => format!(
+                    "{:.1} ms, {}",
+                    millis(longest_gap),
+                    if longest_gap < self.time_to_burnout {
+                        "no burnout"
+                    } else {
+                        "burns out"
+                    }
+                ),
+                Measurement::Starved { coder_id } => {
+                    format!("coder {coder_id} never gets his dongles, burns out")
+                }
+                Measurement::NotMeasured => {
+                    "not measured, chandy-misra runs on the real clock only".to_string()
+                }
+                Measurement::Interrupted => "not measured, interrupted".to_string(),
+            };
+            writeln!(f, "{name:>26} {verdict}")?;
+        }
+
+        writeln!(f)?;
+        if !self.is_feasible() {
+            return write!(
+                f,
+                "impossible: some coder goes {:.1} ms or more without compiling whatever the scheduler, a burnout is expected",
+                millis(minimum_gap)
+            );
+        }
+
+        let burns_out: Vec<Option<bool>> = self
+            .schedulers
+            .iter()
+            .map(|scheduler| scheduler.burns_out(self.time_to_burnout))
+            .collect();
+        if burns_out.contains(&Some(true)) {
+            write!(
+                f,
+                "feasible in theory: a burnout is the scheduler's doing, not the configuration's"
+            )
+        } else if burns_out.contains(&None) {
+            write!(
+                f,
+                "feasible in theory: a burnout can be avoided, but the run itself wasn't measured"
+            )
+        } else {
+            write!(
+                f,
+                "feasible: no burnout expected, with some margin for the real clock"
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The philosophers' table: compile to eat, debug to sleep, no cooldown
+    fn ring(number_of_coders: u32, time_to_burnout: u64) -> Analysis {
+        let args = Args::builder()
+            .number_of_coders(number_of_coders)
+            .time_to_burnout(Duration::from_millis(time_to_burnout))
+            .time_to_compile(Duration::from_millis(200))
+            .time_to_debug(Duration::from_millis(200))
+            .time_to_refactor(Duration::ZERO)
+            .dongle_cooldown(Duration::ZERO)
+            .build()
+            .unwrap();
+        analyze(&args)
+    }
+
+    fn gap_of(analysis: &Analysis, name: &str) -> Option<Duration> {
+        analysis
+            .bounds
+            .iter()
+            .find(|bound| bound.name == name)
+            .map(|bound| bound.gap)
+    }
+
+    #[test]
+    fn an_even_ring_takes_two_rounds() {
+        let analysis = ring(4, 410);
+
+        assert_eq!(
+            gap_of(&analysis, "even ring"),
+            Some(Duration::from_millis(400))
+        );
+        assert_eq!(gap_of(&analysis, "odd ring, first compiles"), None);
+        assert_eq!(analysis.minimum_gap(), Duration::from_millis(400));
+        assert!(analysis.is_feasible());
+        assert_eq!(
+            analysis.schedulers[0].burns_out(analysis.time_to_burnout),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn an_odd_ring_needs_three_compiles() {
+        let analysis = ring(5, 410);
+
+        assert_eq!(
+            gap_of(&analysis, "odd ring, first compiles"),
+            Some(Duration::from_millis(600))
+        );
+        assert_eq!(
+            gap_of(&analysis, "odd ring"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(analysis.minimum_gap(), Duration::from_millis(600));
+        assert!(!analysis.is_feasible());
+
+        assert!(ring(5, 800).is_feasible());
+    }
+
+    #[test]
+    fn a_coder_needs_his_own_cycle() {
+        let args = Args::builder()
+            .number_of_coders(4)
+            .time_to_compile(Duration::from_millis(100))
+            .time_to_debug(Duration::from_millis(50))
+            .time_to_refactor(Duration::from_millis(50))
+            .dongle_cooldown(Duration::from_millis(300))
+            .build()
+            .unwrap();
+
+        // the cooldown outlasts debugging and refactoring
+        let gap = gap_of(&analyze(&args), "own cycle");
+        assert_eq!(gap, Some(Duration::from_millis(400)));
+    }
+}
@@ -1,23 +1,120 @@
-use std::{
-    env::Args as ProgramArgs, error::Error, fmt::Display, num::ParseIntError, time::Duration,
+use std::{collections::BTreeMap, error::Error, fmt::Display, num::ParseIntError, time::Duration};
+
+use crate::{
+    distribution::Distribution,
+    replay::{Recording, ReplayError},
+    scenario::{self, ScenarioError},
+    topology::{Graph, Topology},
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Args {
     pub number_of_coders: u32,
     pub time_to_burnout: Duration,
-    pub time_to_compile: Duration,
-    pub time_to_debug: Duration,
-    pub time_to_refactor: Duration,
+    pub time_to_compile: Distribution,
+    pub time_to_debug: Distribution,
+    pub time_to_refactor: Distribution,
+    pub number_of_compiles_required: u32,
+    pub dongle_cooldown: Distribution,
+    pub scheduler: SchedulerKind,
+    pub topology: Topology,
+    pub backend: Backend,
+    /// where every drawn duration comes from
+    pub seed: u64,
+    pub clock: ClockMode,
+    pub log_format: LogFormat,
+    pub stats: bool,
+    /// look for deadlocks and starving coders while it runs
+    pub watchdog: bool,
+    /// read commands from stdin that pause, slow down or stop the run while it runs
+    pub interactive: bool,
+    /// when a signal stops the run, print what it got to and its statistics on stderr
+    pub interruption_summary: bool,
+    /// where to write the state of the codexion if a coder burns out, `-` for stdout
+    pub post_mortem: Option<String>,
+    /// where to write the run in the Chrome Trace Event format
+    pub trace_out: Option<String>,
+    /// where to write every dongle grant of the run
+    pub record: Option<String>,
+    /// grants of an earlier run, every dongle goes to the same coders in the same order
+    pub replay: Option<Recording>,
+    /// per coder id, what differs from the shared durations
+    pub coder_overrides: BTreeMap<u32, CoderOverride>,
+    /// per dongle id, a cooldown other than `dongle_cooldown`
+    pub dongle_cooldowns: BTreeMap<u32, Distribution>,
+    /// per coder id, the dongle ids he needs instead of the topology's
+    pub coder_dongles: BTreeMap<u32, Vec<u32>>,
+}
+
+/// Settings of one coder that differ from the shared ones
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CoderOverride {
+    pub time_to_compile: Option<Distribution>,
+    pub time_to_debug: Option<Distribution>,
+    pub time_to_refactor: Option<Distribution>,
+    pub number_of_compiles_required: Option<u32>,
+}
+
+/// Settings of one coder, once the overrides are applied
+#[derive(Clone, Copy, Debug)]
+pub struct CoderProfile {
+    pub time_to_compile: Distribution,
+    pub time_to_debug: Distribution,
+    pub time_to_refactor: Distribution,
     pub number_of_compiles_required: u32,
-    pub dongle_cooldown: Duration,
-    pub scheduler: Scheduler,
 }
 
 #[derive(Debug, Clone, Copy)]
-pub enum Scheduler {
-    FIFO,
-    EDF,
+pub enum SchedulerKind {
+    Fifo,
+    Edf,
+    /// least laxity first
+    Llf,
+    /// a coder drawn at random, from `seed` or else `--seed`
+    Lottery {
+        seed: Option<u64>,
+    },
+    RoundRobin,
+    /// priority by coder id, raised by every turn lost
+    Aging,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Dongles are shared behind locks and handed out by the scheduler
+    Locks,
+    /// Coders pass the dongles to each other over channels (Chandy–Misra),
+    /// the scheduler isn't used
+    ChandyMisra,
+    /// One arbiter hands every coder all of his dongles at once, from a
+    /// single waiting line ordered by the scheduler
+    Arbiter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockMode {
+    /// Coders run on their own threads and wait on wall-clock time
+    Real,
+    /// Coders are driven by a discrete-event loop on a virtual clock
+    Virtual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Coloured lines for humans
+    Text,
+    /// One JSON object per event, JSON Lines
+    Json,
+    /// Nothing printed, events only go to observers
+    Quiet,
+    /// A live view of the coders and dongles, redrawn in place
+    Tui,
+}
+
+/// Builds [`Args`] field by field, starting from the defaults
+#[derive(Clone, Debug)]
+pub struct ArgsBuilder {
+    args: Args,
 }
 
 #[derive(Debug)]
@@ -31,91 +128,696 @@ pub enum ArgsError {
         argument: &'static str,
         min_value: u64,
     },
+    InvalidDuration {
+        argument: &'static str,
+        value: String,
+    },
+    InvalidDistribution {
+        argument: &'static str,
+        value: String,
+    },
+    MissingValue(&'static str),
     InvalidScheduler,
+    InvalidBackend(String),
+    /// the Chandy–Misra backend runs on threads only
+    VirtualChandyMisra,
+    /// Chandy–Misra passes each dongle between two coders at most
+    DongleSharedTooWidely(u32),
+    /// the watchdog reads the waiting line of each dongle
+    WatchdogWithoutLocks,
+    /// a virtual run is over before the dashboard is drawn
+    VirtualTui,
+    /// a virtual run is over before a command is read
+    VirtualInteractive,
+    /// commands change the dongles behind their locks
+    InteractiveWithoutLocks,
+    /// grants are recorded and replayed on the waiting line of each dongle
+    GrantsWithoutLocks,
+    UnknownOption(String),
+    UnknownCoder(u32),
+    /// a cooldown for a dongle that doesn't exist
+    UnknownDongle(u32),
+    /// dongle ids a coder is given go from 1 to the topology's dongles plus
+    /// every id given, so they can't ask for billions of dongles
+    InvalidCoderDongle {
+        coder_id: u32,
+        dongle_id: u32,
+        max_dongle_id: u32,
+    },
+    InvalidTopology(String),
+    InvalidDegree {
+        degree: u32,
+        number_of_coders: u32,
+    },
+    CoderWithoutDongle(u32),
+    DongleNeededTwice {
+        coder_id: u32,
+        dongle_id: u32,
+    },
+    InvalidScenario(ScenarioError),
+    InvalidReplay(ReplayError),
+    /// the recording has coders or dongles the run doesn't
+    ForeignRecording(String),
+}
+
+/// An argument that takes a value, by position, by flag or by scenario key
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Field {
+    NumberOfCoders,
+    TimeToBurnout,
+    TimeToCompile,
+    TimeToDebug,
+    TimeToRefactor,
+    NumberOfCompilesRequired,
+    DongleCooldown,
+    Scheduler,
+    Topology,
+    Backend,
+    PostMortem,
+    Seed,
+    TraceOut,
+    Record,
+    Replay,
 }
 
-impl TryFrom<ProgramArgs> for Args {
+impl TryFrom<Vec<String>> for Args {
     type Error = ArgsError;
 
-    fn try_from(args: ProgramArgs) -> Result<Self, Self::Error> {
-        let args: Vec<_> = args.collect();
-        if args.len() != 9 {
-            return Err(ArgsError::InvalidArgumentCount);
+    /// Accepts named flags (`--coders 5 --burnout 1.2s`), or the 8 positional
+    /// arguments in [`Field::POSITIONAL`] order, followed by switches
+    fn try_from(args: Vec<String>) -> Result<Self, Self::Error> {
+        let mut builder = Args::builder();
+        let mut positionals = Vec::new();
+        let mut tokens = args.iter().skip(1);
+
+        while let Some(token) = tokens.next() {
+            let Some(flag) = token.strip_prefix("--") else {
+                positionals.push(token);
+                continue;
+            };
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (flag, None),
+            };
+
+            builder = match name {
+                "virtual" => builder.clock(ClockMode::Virtual),
+                "json" => builder.log_format(LogFormat::Json),
+                "quiet" => builder.log_format(LogFormat::Quiet),
+                "tui" => builder.log_format(LogFormat::Tui),
+                "stats" => builder.stats(true),
+                "watchdog" => builder.watchdog(true),
+                "interactive" => builder.interactive(true),
+                "scenario" => {
+                    let path = match inline_value {
+                        Some(path) => path,
+                        None => tokens.next().ok_or(ArgsError::MissingValue("--scenario"))?,
+                    };
+                    scenario::load(path, builder).map_err(ArgsError::InvalidScenario)?
+                }
+                _ => {
+                    let field = Field::from_flag(name)
+                        .ok_or_else(|| ArgsError::UnknownOption(token.clone()))?;
+                    let value = match inline_value {
+                        Some(value) => value,
+                        None => tokens.next().ok_or(ArgsError::MissingValue(field.flag()))?,
+                    };
+                    field.apply(builder, value, field.flag())?
+                }
+            };
         }
 
-        let number_of_coders: u32 = Self::parse_u32(&args[1], "number_of_coders")?;
-        if number_of_coders < 1 {
-            return Err(ArgsError::InvalidNumberRange {
-                argument: "number_of_coders",
-                min_value: 1,
-            });
+        if !positionals.is_empty() {
+            if positionals.len() != Field::POSITIONAL.len() {
+                return Err(ArgsError::InvalidArgumentCount);
+            }
+            for (field, value) in Field::POSITIONAL.into_iter().zip(positionals) {
+                builder = field.apply(builder, value, field.name())?;
+            }
         }
 
-        let time_to_burnout: u64 = Self::parse_u64(&args[2], "time_to_burnout")?;
-        let time_to_compile: u64 = Self::parse_u64(&args[3], "time_to_compile")?;
-        let time_to_debug: u64 = Self::parse_u64(&args[4], "time_to_debug")?;
-        let time_to_refactor: u64 = Self::parse_u64(&args[5], "time_to_refactor")?;
+        builder.build()
+    }
+}
 
-        let number_of_compiles_required: u32 =
-            Self::parse_u32(&args[6], " number_of_compiles_required")?;
-        if number_of_compiles_required < 1 {
-            return Err(ArgsError::InvalidNumberRange {
-                argument: "number_of_compiles_required",
-                min_value: 1,
-            });
-        }
+impl Field {
+    const POSITIONAL: [Field; 8] = [
+        Field::NumberOfCoders,
+        Field::TimeToBurnout,
+        Field::TimeToCompile,
+        Field::TimeToDebug,
+        Field::TimeToRefactor,
+        Field::NumberOfCompilesRequired,
+        Field::DongleCooldown,
+        Field::Scheduler,
+    ];
 
-        let dongle_cooldown: u64 = Self::parse_u64(&args[7], "dongle_cooldown")?;
+    /// Fields that can only be given by flag or scenario key
+    const NAMED_ONLY: [Field; 7] = [
+        Field::Topology,
+        Field::Backend,
+        Field::PostMortem,
+        Field::Seed,
+        Field::TraceOut,
+        Field::Record,
+        Field::Replay,
+    ];
 
-        let scheduler = args[8].as_str().try_into()?;
+    pub(crate) fn from_flag(flag: &str) -> Option<Self> {
+        Self::POSITIONAL
+            .into_iter()
+            .chain(Self::NAMED_ONLY)
+            .find(|field| field.key() == flag)
+    }
 
-        let time_to_burnout = Duration::from_millis(time_to_burnout);
-        let time_to_compile = Duration::from_millis(time_to_compile);
-        let time_to_debug = Duration::from_millis(time_to_debug);
-        let time_to_refactor = Duration::from_millis(time_to_refactor);
-        let dongle_cooldown = Duration::from_millis(dongle_cooldown);
+    /// Flag without its dashes, also the key in scenario files
+    pub(crate) fn key(self) -> &'static str {
+        &self.flag()[2..]
+    }
 
-        Ok(Self {
-            number_of_coders,
-            time_to_burnout,
-            time_to_compile,
-            time_to_debug,
-            time_to_refactor,
-            number_of_compiles_required,
-            dongle_cooldown,
-            scheduler,
+    fn name(self) -> &'static str {
+        match self {
+            Field::NumberOfCoders => "number_of_coders",
+            Field::TimeToBurnout => "time_to_burnout",
+            Field::TimeToCompile => "time_to_compile",
+            Field::TimeToDebug => "time_to_debug",
+            Field::TimeToRefactor => "time_to_refactor",
+            Field::NumberOfCompilesRequired => "number_of_compiles_required",
+            Field::DongleCooldown => "dongle_cooldown",
+            Field::Scheduler => "scheduler",
+            Field::Topology => "topology",
+            Field::Backend => "backend",
+            Field::PostMortem => "post_mortem",
+            Field::Seed => "seed",
+            Field::TraceOut => "trace_out",
+            Field::Record => "record",
+            Field::Replay => "replay",
+        }
+    }
+
+    fn flag(self) -> &'static str {
+        match self {
+            Field::NumberOfCoders => "--coders",
+            Field::TimeToBurnout => "--burnout",
+            Field::TimeToCompile => "--compile",
+            Field::TimeToDebug => "--debug",
+            Field::TimeToRefactor => "--refactor",
+            Field::NumberOfCompilesRequired => "--compiles",
+            Field::DongleCooldown => "--cooldown",
+            Field::Scheduler => "--scheduler",
+            Field::Topology => "--topology",
+            Field::Backend => "--backend",
+            Field::PostMortem => "--post-mortem",
+            Field::Seed => "--seed",
+            Field::TraceOut => "--trace-out",
+            Field::Record => "--record",
+            Field::Replay => "--replay",
+        }
+    }
+
+    /// Parse the value and set it, errors name the argument as the user wrote it
+    pub(crate) fn apply(
+        self,
+        builder: ArgsBuilder,
+        value: &str,
+        argument: &'static str,
+    ) -> Result<ArgsBuilder, ArgsError> {
+        Ok(match self {
+            Field::NumberOfCoders => builder.number_of_coders(Args::parse_count(value, argument)?),
+            Field::TimeToBurnout => builder.time_to_burnout(Args::parse_duration(value, argument)?),
+            Field::TimeToCompile => builder.time_to_compile(Distribution::parse(value, argument)?),
+            Field::TimeToDebug => builder.time_to_debug(Distribution::parse(value, argument)?),
+            Field::TimeToRefactor => {
+                builder.time_to_refactor(Distribution::parse(value, argument)?)
+            }
+            Field::NumberOfCompilesRequired => {
+                builder.number_of_compiles_required(Args::parse_count(value, argument)?)
+            }
+            Field::DongleCooldown => builder.dongle_cooldown(Distribution::parse(value, argument)?),
+            Field::Scheduler => builder.scheduler(value.try_into()?),
+            Field::Topology => builder.topology(value.try_into()?),
+            Field::Backend => builder.backend(value.try_into()?),
+            Field::PostMortem => builder.post_mortem(value),
+            Field::TraceOut => builder.trace_out(value),
+            Field::Record => builder.record(value),
+            Field::Replay => {
+                builder.replay(Recording::load(value).map_err(ArgsError::InvalidReplay)?)
+            }
+            Field::Seed => builder.seed(
+                value
+                    .parse()
+                    .map_err(|source| ArgsError::InvalidNumber { argument, source })?,
+            ),
         })
     }
 }
 
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            number_of_coders: 5,
+            time_to_burnout: Duration::from_millis(800),
+            time_to_compile: Duration::from_millis(200).into(),
+            time_to_debug: Duration::from_millis(200).into(),
+            time_to_refactor: Duration::from_millis(200).into(),
+            number_of_compiles_required: 3,
+            dongle_cooldown: Duration::from_millis(10).into(),
+            scheduler: SchedulerKind::Fifo,
+            topology: Topology::Ring,
+            backend: Backend::Locks,
+            seed: 0,
+            clock: ClockMode::Real,
+            log_format: LogFormat::Text,
+            stats: false,
+            watchdog: false,
+            interactive: false,
+            interruption_summary: true,
+            post_mortem: None,
+            trace_out: None,
+            record: None,
+            replay: None,
+            coder_overrides: BTreeMap::new(),
+            dongle_cooldowns: BTreeMap::new(),
+            coder_dongles: BTreeMap::new(),
+        }
+    }
+}
+
 impl Args {
-    fn parse_u64(value: &String, arg_name: &'static str) -> Result<u64, ArgsError> {
+    pub fn builder() -> ArgsBuilder {
+        ArgsBuilder {
+            args: Args::default(),
+        }
+    }
+
+    /// Settings of the coder `coder_id`, with his overrides applied
+    pub fn coder_profile(&self, coder_id: u32) -> CoderProfile {
+        let coder_override = self
+            .coder_overrides
+            .get(&coder_id)
+            .copied()
+            .unwrap_or_default();
+
+        CoderProfile {
+            time_to_compile: coder_override
+                .time_to_compile
+                .unwrap_or(self.time_to_compile),
+            time_to_debug: coder_override.time_to_debug.unwrap_or(self.time_to_debug),
+            time_to_refactor: coder_override
+                .time_to_refactor
+                .unwrap_or(self.time_to_refactor),
+            number_of_compiles_required: coder_override
+                .number_of_compiles_required
+                .unwrap_or(self.number_of_compiles_required),
+        }
+    }
+
+    /// Cooldown of the dongle `dongle_id`, with its override applied
+    pub fn cooldown_of(&self, dongle_id: u32) -> Distribution {
+        self.dongle_cooldowns
+            .get(&dongle_id)
+            .copied()
+            .unwrap_or(self.dongle_cooldown)
+    }
+
+    /// Whether any phase or cooldown is drawn from a distribution that isn't constant
+    pub fn random_durations(&self) -> bool {
+        let overrides = self.coder_overrides.values().flat_map(|coder_override| {
+            [
+                coder_override.time_to_compile,
+                coder_override.time_to_debug,
+                coder_override.time_to_refactor,
+            ]
+        });
+
+        [
+            self.time_to_compile,
+            self.time_to_debug,
+            self.time_to_refactor,
+            self.dongle_cooldown,
+        ]
+        .into_iter()
+        .chain(overrides.flatten())
+        .chain(self.dongle_cooldowns.values().copied())
+        .any(|distribution| !distribution.is_constant())
+    }
+
+    /// Dongles of every coder: the topology's, replaced by the given ones
+    pub fn graph(&self) -> Graph {
+        let mut graph = self.topology.graph(self.number_of_coders);
+
+        for (&coder_id, dongle_ids) in &self.coder_dongles {
+            let dongles = dongle_ids.iter().map(|&id| id as usize - 1).collect();
+            graph.coders[coder_id as usize - 1] = dongles;
+            graph.number_of_dongles = dongle_ids
+                .iter()
+                .copied()
+                .fold(graph.number_of_dongles, u32::max);
+        }
+        graph.sort();
+        graph
+    }
+
+    fn parse_u32(value: &str, arg_name: &'static str) -> Result<u32, ArgsError> {
         value.parse().map_err(|source| ArgsError::InvalidNumber {
             argument: arg_name,
             source,
         })
     }
 
-    fn parse_u32(value: &String, arg_name: &'static str) -> Result<u32, ArgsError> {
-        value.parse().map_err(|source| ArgsError::InvalidNumber {
+    /// A `u32` of at least 1
+    pub(crate) fn parse_count(value: &str, arg_name: &'static str) -> Result<u32, ArgsError> {
+        let count = Self::parse_u32(value, arg_name)?;
+        if count < 1 {
+            return Err(ArgsError::InvalidNumberRange {
+                argument: arg_name,
+                min_value: 1,
+            });
+        }
+        Ok(count)
+    }
+
+    /// A decimal number with a `us`, `ms`, `s` or `m` suffix, millis without suffix
+    pub(crate) fn parse_duration(
+        value: &str,
+        arg_name: &'static str,
+    ) -> Result<Duration, ArgsError> {
+        let invalid = || ArgsError::InvalidDuration {
             argument: arg_name,
-            source,
-        })
+            value: value.to_string(),
+        };
+
+        let split = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+
+        let nanos_per_unit: f64 = match unit {
+            "us" | "µs" => 1e3,
+            "" | "ms" => 1e6,
+            "s" => 1e9,
+            "m" => 60e9,
+            _ => return Err(invalid()),
+        };
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+
+        Ok(Duration::from_nanos(
+            (number * nanos_per_unit).round() as u64
+        ))
+    }
+}
+
+impl ArgsBuilder {
+    pub fn number_of_coders(mut self, number_of_coders: u32) -> Self {
+        self.args.number_of_coders = number_of_coders;
+        self
+    }
+
+    pub fn time_to_burnout(mut self, time_to_burnout: Duration) -> Self {
+        self.args.time_to_burnout = time_to_burnout;
+        self
+    }
+
+    pub fn time_to_compile(mut self, time_to_compile: impl Into<Distribution>) -> Self {
+        self.args.time_to_compile = time_to_compile.into();
+        self
+    }
+
+    pub fn time_to_debug(mut self, time_to_debug: impl Into<Distribution>) -> Self {
+        self.args.time_to_debug = time_to_debug.into();
+        self
+    }
+
+    pub fn time_to_refactor(mut self, time_to_refactor: impl Into<Distribution>) -> Self {
+        self.args.time_to_refactor = time_to_refactor.into();
+        self
+    }
+
+    pub fn number_of_compiles_required(mut self, number_of_compiles_required: u32) -> Self {
+        self.args.number_of_compiles_required = number_of_compiles_required;
+        self
+    }
+
+    pub fn dongle_cooldown(mut self, dongle_cooldown: impl Into<Distribution>) -> Self {
+        self.args.dongle_cooldown = dongle_cooldown.into();
+        self
+    }
+
+    pub fn scheduler(mut self, scheduler: SchedulerKind) -> Self {
+        self.args.scheduler = scheduler;
+        self
+    }
+
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.args.topology = topology;
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.args.backend = backend;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.args.seed = seed;
+        self
+    }
+
+    pub fn clock(mut self, clock: ClockMode) -> Self {
+        self.args.clock = clock;
+        self
+    }
+
+    pub fn log_format(mut self, log_format: LogFormat) -> Self {
+        self.args.log_format = log_format;
+        self
+    }
+
+    pub fn stats(mut self, stats: bool) -> Self {
+        self.args.stats = stats;
+        self
+    }
+
+    pub fn watchdog(mut self, watchdog: bool) -> Self {
+        self.args.watchdog = watchdog;
+        self
+    }
+
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.args.interactive = interactive;
+        self
+    }
+
+    pub fn interruption_summary(mut self, interruption_summary: bool) -> Self {
+        self.args.interruption_summary = interruption_summary;
+        self
+    }
+
+    /// Write the post-mortem of a burnout to `path`, `-` for stdout
+    pub fn post_mortem(mut self, path: &str) -> Self {
+        self.args.post_mortem = Some(path.to_string());
+        self
+    }
+
+    /// Write the run as a Chrome trace to `path`
+    pub fn trace_out(mut self, path: &str) -> Self {
+        self.args.trace_out = Some(path.to_string());
+        self
+    }
+
+    /// Write every dongle grant of the run to `path`
+    pub fn record(mut self, path: &str) -> Self {
+        self.args.record = Some(path.to_string());
+        self
+    }
+
+    /// Hand every dongle to the coders of `recording`, in its order
+    pub fn replay(mut self, recording: Recording) -> Self {
+        self.args.replay = Some(recording);
+        self
+    }
+
+    /// Give the coder `coder_id` his own settings, replacing earlier ones
+    pub fn coder_override(mut self, coder_id: u32, coder_override: CoderOverride) -> Self {
+        self.args.coder_overrides.insert(coder_id, coder_override);
+        self
+    }
+
+    /// Give the dongle `dongle_id` its own cooldown
+    pub fn dongle_cooldown_of(mut self, dongle_id: u32, cooldown: impl Into<Distribution>) -> Self {
+        self.args
+            .dongle_cooldowns
+            .insert(dongle_id, cooldown.into());
+        self
+    }
+
+    /// Make the coder `coder_id` need the dongles `dongle_ids`, whatever the topology
+    pub fn coder_dongles(mut self, coder_id: u32, dongle_ids: Vec<u32>) -> Self {
+        self.args.coder_dongles.insert(coder_id, dongle_ids);
+        self
+    }
+
+    /// Check the ranges and give the arguments
+    pub fn build(self) -> Result<Args, ArgsError> {
+        if self.args.number_of_coders < 1 {
+            return Err(ArgsError::InvalidNumberRange {
+                argument: "number_of_coders",
+                min_value: 1,
+            });
+        }
+        if self.args.number_of_compiles_required < 1 {
+            return Err(ArgsError::InvalidNumberRange {
+                argument: "number_of_compiles_required",
+                min_value: 1,
+            });
+        }
+
+        self.args.topology.validate(self.args.number_of_coders)?;
+
+        let valid_id = |id: &u32| (1..=self.args.number_of_coders).contains(id);
+        let coder_ids = self
+            .args
+            .coder_overrides
+            .keys()
+            .chain(self.args.coder_dongles.keys());
+        if let Some(&coder_id) = coder_ids.into_iter().find(|id| !valid_id(id)) {
+            return Err(ArgsError::UnknownCoder(coder_id));
+        }
+        let given_dongles: usize = self.args.coder_dongles.values().map(Vec::len).sum();
+        let max_dongle_id = u32::try_from(given_dongles)
+            .ok()
+            .and_then(|given| {
+                let topology = self.args.topology.graph(self.args.number_of_coders);
+                topology.number_of_dongles.checked_add(given)
+            })
+            .unwrap_or(u32::MAX);
+        for (&coder_id, dongle_ids) in &self.args.coder_dongles {
+            for (idx, &dongle_id) in dongle_ids.iter().enumerate() {
+                if !(1..=max_dongle_id).contains(&dongle_id) {
+                    return Err(ArgsError::InvalidCoderDongle {
+                        coder_id,
+                        dongle_id,
+                        max_dongle_id,
+                    });
+                }
+                if dongle_ids[..idx].contains(&dongle_id) {
+                    return Err(ArgsError::DongleNeededTwice {
+                        coder_id,
+                        dongle_id,
+                    });
+                }
+            }
+        }
+
+        let graph = self.args.graph();
+        if let Some(idx) = graph.coders.iter().position(Vec::is_empty) {
+            return Err(ArgsError::CoderWithoutDongle(idx as u32 + 1));
+        }
+        let valid_dongle_id = |id: &u32| (1..=graph.number_of_dongles).contains(id);
+        if let Some(&dongle_id) = self
+            .args
+            .dongle_cooldowns
+            .keys()
+            .find(|id| !valid_dongle_id(id))
+        {
+            return Err(ArgsError::UnknownDongle(dongle_id));
+        }
+
+        if self.args.backend == Backend::ChandyMisra {
+            if self.args.clock == ClockMode::Virtual {
+                return Err(ArgsError::VirtualChandyMisra);
+            }
+            let mut sharers = vec![0; graph.number_of_dongles as usize];
+            for dongles in &graph.coders {
+                let mut dongles = dongles.clone();
+                dongles.dedup();
+                for dongle_idx in dongles {
+                    sharers[dongle_idx] += 1;
+                }
+            }
+            if let Some(idx) = sharers.iter().position(|&count| count > 2) {
+                return Err(ArgsError::DongleSharedTooWidely(idx as u32 + 1));
+            }
+        }
+        if self.args.watchdog && self.args.backend != Backend::Locks {
+            return Err(ArgsError::WatchdogWithoutLocks);
+        }
+        if self.args.log_format == LogFormat::Tui && self.args.clock == ClockMode::Virtual {
+            return Err(ArgsError::VirtualTui);
+        }
+        if self.args.interactive && self.args.clock == ClockMode::Virtual {
+            return Err(ArgsError::VirtualInteractive);
+        }
+        if self.args.interactive && self.args.backend != Backend::Locks {
+            return Err(ArgsError::InteractiveWithoutLocks);
+        }
+        if (self.args.record.is_some() || self.args.replay.is_some())
+            && self.args.backend != Backend::Locks
+        {
+            return Err(ArgsError::GrantsWithoutLocks);
+        }
+        if let Some(recording) = &self.args.replay
+            && recording
+                .grants
+                .iter()
+                .any(|grant| !valid_id(&grant.coder_id) || !valid_dongle_id(&grant.dongle_id))
+        {
+            return Err(ArgsError::ForeignRecording(recording.path.clone()));
+        }
+        if self
+            .args
+            .coder_overrides
+            .values()
+            .any(|coder_override| coder_override.number_of_compiles_required == Some(0))
+        {
+            return Err(ArgsError::InvalidNumberRange {
+                argument: "number_of_compiles_required",
+                min_value: 1,
+            });
+        }
+        Ok(self.args)
     }
 }
 
-impl TryFrom<&str> for Scheduler {
+impl TryFrom<&str> for SchedulerKind {
     type Error = ArgsError;
 
+    /// `fifo`, `edf`, `llf`, `lottery[:SEED]`, `round-robin` or `aging`
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        match value {
-            "fifo" => Ok(Scheduler::FIFO),
-            "edf" => Ok(Scheduler::EDF),
+        let (name, seed) = match value.split_once(':') {
+            Some((name, seed)) => (name, Some(seed)),
+            None => (value, None),
+        };
+
+        match (name, seed) {
+            ("fifo", None) => Ok(SchedulerKind::Fifo),
+            ("edf", None) => Ok(SchedulerKind::Edf),
+            ("llf", None) => Ok(SchedulerKind::Llf),
+            ("lottery", None) => Ok(SchedulerKind::Lottery { seed: None }),
+            ("lottery", Some(seed)) => seed
+                .parse()
+                .map(|seed| SchedulerKind::Lottery { seed: Some(seed) })
+                .map_err(|_| ArgsError::InvalidScheduler),
+            ("round-robin", None) => Ok(SchedulerKind::RoundRobin),
+            ("aging", None) => Ok(SchedulerKind::Aging),
             _ => Err(ArgsError::InvalidScheduler),
         }
     }
 }
 
+impl TryFrom<&str> for Backend {
+    type Error = ArgsError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "locks" => Ok(Backend::Locks),
+            "chandy-misra" => Ok(Backend::ChandyMisra),
+            "arbiter" => Ok(Backend::Arbiter),
+            _ => Err(ArgsError::InvalidBackend(value.to_string())),
+        }
+    }
+}
+
 impl Display for ArgsError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -131,9 +833,276 @@ impl Display for ArgsError {
                 f,
                 "Error: invalid number range for argument '{argument}' (min_value: {min_value})"
             ),
-            ArgsError::InvalidScheduler => write!(f, "Error: invalid scheduler"),
+            ArgsError::InvalidDuration { argument, value } => write!(
+                f,
+                "Error: invalid duration '{value}' for argument '{argument}' (expected e.g. 500us, 200ms, 1.2s, 2m)"
+            ),
+            ArgsError::InvalidDistribution { argument, value } => write!(
+                f,
+                "Error: invalid distribution '{value}' for argument '{argument}' (expected a duration, uniform:MIN:MAX, normal:MEAN:STDDEV, exponential:MEAN or lognormal:MEAN:STDDEV)"
+            ),
+            ArgsError::MissingValue(argument) => {
+                write!(f, "Error: missing value for argument '{argument}'")
+            }
+            ArgsError::InvalidScheduler => write!(
+                f,
+                "Error: invalid scheduler (expected fifo, edf, llf, lottery[:SEED], round-robin or aging)"
+            ),
+            ArgsError::InvalidBackend(value) => write!(
+                f,
+                "Error: invalid backend '{value}' (expected locks, chandy-misra or arbiter)"
+            ),
+            ArgsError::VirtualChandyMisra => write!(
+                f,
+                "Error: the chandy-misra backend runs on threads, it can't use the virtual clock"
+            ),
+            ArgsError::DongleSharedTooWidely(dongle_id) => write!(
+                f,
+                "Error: dongle {dongle_id} is needed by more than two coders, chandy-misra can only pass it between two"
+            ),
+            ArgsError::WatchdogWithoutLocks => write!(
+                f,
+                "Error: the watchdog watches the waiting line of each dongle, it needs the locks backend"
+            ),
+            ArgsError::GrantsWithoutLocks => write!(
+                f,
+                "Error: grants are recorded and replayed on the waiting line of each dongle, it needs the locks backend"
+            ),
+            ArgsError::VirtualTui => write!(
+                f,
+                "Error: the dashboard follows the real clock, a virtual run is over before it's drawn"
+            ),
+            ArgsError::VirtualInteractive => write!(
+                f,
+                "Error: commands are read while the coders run on the real clock, a virtual run is over before the first one"
+            ),
+            ArgsError::InteractiveWithoutLocks => write!(
+                f,
+                "Error: commands pause the coders and change the dongles behind their locks, the interactive mode needs the locks backend"
+            ),
+            ArgsError::UnknownOption(option) => write!(f, "Error: unknown option '{option}'"),
+            ArgsError::UnknownCoder(coder_id) => {
+                write!(
+                    f,
+                    "Error: settings given for coder {coder_id}, who doesn't exist"
+                )
+            }
+            ArgsError::UnknownDongle(dongle_id) => {
+                write!(
+                    f,
+                    "Error: cooldown given for dongle {dongle_id}, which doesn't exist"
+                )
+            }
+            ArgsError::InvalidCoderDongle {
+                coder_id,
+                dongle_id,
+                max_dongle_id,
+            } => write!(
+                f,
+                "Error: coder {coder_id} is given dongle {dongle_id}, dongle ids go from 1 to {max_dongle_id}"
+            ),
+            ArgsError::InvalidTopology(value) => write!(
+                f,
+                "Error: invalid topology '{value}' (expected ring, star, complete, grid:COLUMNS or random:DEGREE[:SEED])"
+            ),
+            ArgsError::InvalidDegree {
+                degree,
+                number_of_coders,
+            } => write!(
+                f,
+                "Error: random topology of degree {degree} needs between 1 and {number_of_coders} dongles per coder"
+            ),
+            ArgsError::CoderWithoutDongle(coder_id) => {
+                write!(f, "Error: coder {coder_id} needs no dongle")
+            }
+            ArgsError::DongleNeededTwice {
+                coder_id,
+                dongle_id,
+            } => write!(
+                f,
+                "Error: coder {coder_id} needs dongle {dongle_id} more than once"
+            ),
+            ArgsError::InvalidScenario(source) => write!(f, "{source}"),
+            ArgsError::InvalidReplay(source) => write!(f, "{source}"),
+            ArgsError::ForeignRecording(path) => write!(
+                f,
+                "Error: recording '{path}' grants dongles or coders this run doesn't have, it was recorded with other arguments"
+            ),
         }
     }
 }
 
 impl Error for ArgsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::Grant;
+
+    fn build(builder: ArgsBuilder) -> ArgsError {
+        builder
+            .build()
+            .expect_err("the arguments should be invalid")
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let args = Args::builder().build().unwrap();
+        assert_eq!(args.number_of_coders, 5);
+        assert_eq!(args.graph().number_of_dongles, 5);
+    }
+
+    #[test]
+    fn counts_start_at_one() {
+        assert!(matches!(
+            build(Args::builder().number_of_coders(0)),
+            ArgsError::InvalidNumberRange {
+                argument: "number_of_coders",
+                ..
+            }
+        ));
+        assert!(matches!(
+            build(Args::builder().number_of_compiles_required(0)),
+            ArgsError::InvalidNumberRange {
+                argument: "number_of_compiles_required",
+                ..
+            }
+        ));
+        let coder_override = CoderOverride {
+            number_of_compiles_required: Some(0),
+            ..CoderOverride::default()
+        };
+        assert!(matches!(
+            build(Args::builder().coder_override(2, coder_override)),
+            ArgsError::InvalidNumberRange { .. }
+        ));
+    }
+
+    #[test]
+    fn settings_must_name_existing_coders_and_dongles() {
+        assert!(matches!(
+            build(Args::builder().coder_override(6, CoderOverride::default())),
+            ArgsError::UnknownCoder(6)
+        ));
+        assert!(matches!(
+            build(Args::builder().coder_dongles(9, vec![1])),
+            ArgsError::UnknownCoder(9)
+        ));
+        assert!(matches!(
+            build(Args::builder().dongle_cooldown_of(6, Duration::from_millis(5))),
+            ArgsError::UnknownDongle(6)
+        ));
+        assert!(matches!(
+            build(Args::builder().coder_dongles(1, vec![0])),
+            ArgsError::InvalidCoderDongle {
+                coder_id: 1,
+                dongle_id: 0,
+                ..
+            }
+        ));
+        // 5 dongles in the ring and 2 ids given
+        assert!(matches!(
+            build(Args::builder().coder_dongles(1, vec![1, 4_000_000_000])),
+            ArgsError::InvalidCoderDongle {
+                coder_id: 1,
+                dongle_id: 4_000_000_000,
+                max_dongle_id: 7
+            }
+        ));
+        let args = Args::builder()
+            .coder_dongles(1, vec![1, 7])
+            .build()
+            .unwrap();
+        assert_eq!(args.graph().number_of_dongles, 7);
+    }
+
+    #[test]
+    fn coder_dongles_are_needed_once() {
+        assert!(matches!(
+            build(Args::builder().coder_dongles(2, vec![3, 1, 3])),
+            ArgsError::DongleNeededTwice {
+                coder_id: 2,
+                dongle_id: 3
+            }
+        ));
+        assert!(matches!(
+            build(Args::builder().coder_dongles(2, vec![])),
+            ArgsError::CoderWithoutDongle(2)
+        ));
+    }
+
+    #[test]
+    fn random_topology_degree_fits_the_coders() {
+        let topology = Topology::Regular { degree: 6, seed: 0 };
+        assert!(matches!(
+            build(Args::builder().topology(topology)),
+            ArgsError::InvalidDegree {
+                degree: 6,
+                number_of_coders: 5
+            }
+        ));
+    }
+
+    #[test]
+    fn chandy_misra_runs_on_threads_between_two_coders() {
+        let chandy_misra = || Args::builder().backend(Backend::ChandyMisra);
+        assert!(chandy_misra().build().is_ok());
+        assert!(matches!(
+            build(chandy_misra().clock(ClockMode::Virtual)),
+            ArgsError::VirtualChandyMisra
+        ));
+        assert!(matches!(
+            build(chandy_misra().topology(Topology::Star)),
+            ArgsError::DongleSharedTooWidely(1)
+        ));
+    }
+
+    #[test]
+    fn real_clock_and_locks_only_features() {
+        assert!(matches!(
+            build(Args::builder().watchdog(true).backend(Backend::Arbiter)),
+            ArgsError::WatchdogWithoutLocks
+        ));
+        assert!(matches!(
+            build(
+                Args::builder()
+                    .log_format(LogFormat::Tui)
+                    .clock(ClockMode::Virtual)
+            ),
+            ArgsError::VirtualTui
+        ));
+        assert!(matches!(
+            build(Args::builder().interactive(true).clock(ClockMode::Virtual)),
+            ArgsError::VirtualInteractive
+        ));
+        assert!(matches!(
+            build(Args::builder().interactive(true).backend(Backend::Arbiter)),
+            ArgsError::InteractiveWithoutLocks
+        ));
+        assert!(matches!(
+            build(
+                Args::builder()
+                    .record("run.jsonl")
+                    .backend(Backend::Arbiter)
+            ),
+            ArgsError::GrantsWithoutLocks
+        ));
+    }
+
+    #[test]
+    fn replay_must_match_the_run() {
+        let recording = |coder_id| Recording {
+            path: "run.jsonl".to_string(),
+            grants: vec![Grant {
+                dongle_id: 1,
+                coder_id,
+                elapsed: Duration::ZERO,
+            }],
+        };
+        assert!(Args::builder().replay(recording(5)).build().is_ok());
+        assert!(matches!(
+            build(Args::builder().replay(recording(6))),
+            ArgsError::ForeignRecording(path) if path == "run.jsonl"
+        ));
+    }
+}
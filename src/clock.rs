@@ -0,0 +1,40 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Source of the current time for the simulation
+pub enum Clock {
+    /// Wall-clock time, threads block on it for real
+    Real,
+    /// Virtual time, it only moves when the simulation advances it
+    Virtual(Mutex<Instant>),
+}
+
+impl Clock {
+    pub fn new_virtual(start: Instant) -> Self {
+        Clock::Virtual(Mutex::new(start))
+    }
+
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::Real => Instant::now(),
+            Clock::Virtual(now) => *now.lock().unwrap(),
+        }
+    }
+
+    /// Jump the virtual clock forward, does nothing on a real clock
+    pub fn advance_to(&self, instant: Instant) {
+        if let Clock::Virtual(now) = self {
+            let mut now = now.lock().unwrap();
+            if instant > *now {
+                *now = instant;
+            }
+        }
+    }
+}
+
+/// A duration in milliseconds, to print with a decimal
+pub(crate) fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    args::Args,
+    codexion::{
+        dongle::{DongleSnapshot, DongleUsage},
+        scheduler::{Request, Scheduler},
+        stats::DongleStatistics,
+    },
+    distribution::Distribution,
+    rng::Rng,
+    topology::Graph,
+};
+
+/// One waiting line for the whole codexion, a coder gets all of his dongles
+/// at once or none of them.
+///
+/// Coders are granted in the scheduler's order, so the order is enforced
+/// across the whole codexion instead of per dongle. A coder who can't be
+/// granted yet reserves his dongles from when they'll all be free, a coder
+/// later in line only gets them if he'll have compiled and they'll have
+/// cooled down by then.
+pub struct Waiter {
+    arbiter: Mutex<Arbiter>,
+    inbox: Mutex<Receiver<Message>>,
+    outbox: Sender<Message>,
+    /// per coder, where his grants go when running on threads
+    grants: Vec<Sender<bool>>,
+}
+
+struct Arbiter {
+    scheduling: Box<dyn Scheduler>,
+    /// per coder, the dongles he needs
+    needs: Vec<Vec<usize>>,
+    /// per coder, on average, to plan ahead
+    compile_times: Vec<Duration>,
+    dongles: Vec<Slot>,
+    /// per coder, when he asked, while he waits
+    asked: Vec<Option<Instant>>,
+}
+
+struct Slot {
+    cooldown: Distribution,
+    rng: Rng,
+    /// drawn when it's handed out, the cooldown it starts when given back
+    next_cooldown: Duration,
+    holder: Option<u32>,
+    /// while held, when the holder should be done compiling
+    held_until: Option<Instant>,
+    ready_at: Option<Instant>,
+    usage: DongleUsage,
+}
+
+enum Message {
+    Ask {
+        coder_idx: usize,
+        last_compile_time: Instant,
+        at: Instant,
+    },
+    Release {
+        coder_idx: usize,
+        at: Instant,
+    },
+    Stop,
+}
+
+/// A coder's line to the waiter
+pub struct Seat {
+    coder_idx: usize,
+    /// in the global order
+    pub dongle_ids: Vec<u32>,
+    waiter: Arc<Waiter>,
+    grants: Mutex<Receiver<bool>>,
+}
+
+/// Build the waiter and the seat of every coder of the graph
+pub fn seat(
+    graph: &Graph,
+    args: &Args,
+    scheduling: Box<dyn Scheduler>,
+) -> (Arc<Waiter>, Vec<Seat>) {
+    let (outbox, inbox) = mpsc::channel();
+    let (grants, receivers): (Vec<_>, Vec<_>) =
+        graph.coders.iter().map(|_| mpsc::channel()).unzip();
+
+    let waiter = Arc::new(Waiter {
+        arbiter: Mutex::new(Arbiter {
+            scheduling,
+            needs: graph.coders.clone(),
+            compile_times: (1..=graph.coders.len() as u32)
+                .map(|coder_id| args.coder_profile(coder_id).time_to_compile.mean())
+                .collect(),
+            dongles: (0..graph.number_of_dongles)
+                .map(|idx| Slot {
+                    cooldown: args.cooldown_of(idx + 1),
+                    rng: Rng::for_dongle(args.seed, idx + 1),
+                    next_cooldown: Duration::ZERO,
+                    holder: None,
+                    held_until: None,
+                    ready_at: None,
+                    usage: DongleUsage::default(),
+                })
+                .collect(),
+            asked: vec![None; graph.coders.len()],
+        }),
+        inbox: Mutex::new(inbox),
+        outbox,
+        grants,
+    });
+
+    let seats = receivers
+        .into_iter()
+        .enumerate()
+        .map(|(coder_idx, grants)| Seat {
+            coder_idx,
+            dongle_ids: graph.coders[coder_idx]
+                .iter()
+                .map(|&idx| idx as u32 + 1)
+                .collect(),
+            waiter: Arc::clone(&waiter),
+            grants: Mutex::new(grants),
+        })
+        .collect();
+
+    (waiter, seats)
+}
+
+impl Waiter {
+    /// Put the coder in the line
+    pub fn ask(&self, coder_idx: usize, last_compile_time: Instant, now: Instant) {
+        self.arbiter
+            .lock()
+            .unwrap()
+            .ask(coder_idx, last_compile_time, now);
+    }
+
+    /// Give back the coder's dongles, they cool down from `now`
+    pub fn release(&self, coder_idx: usize, now: Instant) {
+        self.arbiter.lock().unwrap().release(coder_idx, now);
+    }
+
+    /// Cooldowns the coder's dongles start when he gives them back, in his order
+    pub fn cooldowns(&self, coder_idx: usize) -> Vec<Duration> {
+        let arbiter = self.arbiter.lock().unwrap();
+        arbiter.needs[coder_idx]
+            .iter()
+            .map(|&dongle_idx| arbiter.dongles[dongle_idx].next_cooldown)
+            .collect()
+    }
+
+    /// Coders who get their dongles at `now`, in line order
+    pub fn grant(&self, now: Instant) -> Vec<usize> {
+        self.arbiter.lock().unwrap().grant(now)
+    }
+
+    /// Serve the seats until [`Waiter::stop`], on the arbiter thread
+    pub fn run(&self) {
+        let inbox = self.inbox.lock().unwrap();
+
+        loop {
+            let next_ready = self.arbiter.lock().unwrap().next_ready(Instant::now());
+            let message = match next_ready {
+                Some(ready_at) => {
+                    let timeout = ready_at.saturating_duration_since(Instant::now());
+                    match inbox.recv_timeout(timeout) {
+                        Ok(message) => Some(message),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => Some(Message::Stop),
+                    }
+                }
+                None => Some(inbox.recv().unwrap_or(Message::Stop)),
+            };
+
+            match message {
+                Some(Message::Ask {
+                    coder_idx,
+                    last_compile_time,
+                    at,
+                }) => self.ask(coder_idx, last_compile_time, at),
+                Some(Message::Release { coder_idx, at }) => self.release(coder_idx, at),
+                Some(Message::Stop) => break,
+                // a cooldown is over
+                None => (),
+            }
+
+            for coder_idx in self.grant(Instant::now()) {
+                let _ = self.grants[coder_idx].send(true);
+            }
+        }
+
+        // let the coders still waiting notice the stop
+        for grants in &self.grants {
+            let _ = grants.send(false);
+        }
+    }
+
+    pub fn stop(&self) {
+        let _ = self.outbox.send(Message::Stop);
+    }
+
+    /// Who holds each dongle, and who in the line needs it
+    pub fn snapshot(&self, now: Instant) -> Vec<DongleSnapshot> {
+        let arbiter = self.arbiter.lock().unwrap();
+        let line = arbiter.scheduling.waiting();
+
+        (1..)
+            .zip(&arbiter.dongles)
+            .enumerate()
+            .map(|(dongle_idx, (id, slot))| DongleSnapshot {
+                id,
+                holder: slot.holder,
+                cooldown_left: slot
+                    .ready_at
+                    .filter(|&ready_at| slot.holder.is_none() && now < ready_at)
+                    .map(|ready_at| ready_at - now),
+                waiting: line
+                    .iter()
+                    .copied()
+                    .filter(|&coder_id| arbiter.needs[coder_id as usize - 1].contains(&dongle_idx))
+                    .collect(),
+                passed_over: Vec::new(),
+            })
+            .collect()
+    }
+
+    pub fn statistics(&self, start: Instant, end: Instant) -> Vec<DongleStatistics> {
+        let arbiter = self.arbiter.lock().unwrap();
+        (1..)
+            .zip(&arbiter.dongles)
+            .map(|(dongle_id, slot)| slot.usage.statistics(dongle_id, start, end))
+            .collect()
+    }
+}
+
+impl Arbiter {
+    fn ask(&mut self, coder_idx: usize, last_compile_time: Instant, now: Instant) {
+        self.scheduling.enqueue(Request {
+            coder_id: coder_idx as u32 + 1,
+            last_compile_time,
+        });
+        self.asked[coder_idx] = Some(now);
+    }
+
+    fn release(&mut self, coder_idx: usize, now: Instant) {
+        for &dongle_idx in &self.needs[coder_idx] {
+            let slot = &mut self.dongles[dongle_idx];
+            slot.holder = None;
+            slot.held_until = None;
+            slot.ready_at = Some(now + slot.next_cooldown);
+            slot.usage.record_release(now, slot.next_cooldown);
+        }
+    }
+
+    fn grant(&mut self, now: Instant) -> Vec<usize> {
+        let mut granted = Vec::new();
+        // dongles someone earlier in line waits for, from when he can have them
+        let mut reserved: BTreeMap<usize, Instant> = BTreeMap::new();
+        self.scheduling.choose();
+
+        for coder_id in self.scheduling.waiting() {
+            let coder_idx = coder_id as usize - 1;
+            let needs = &self.needs[coder_idx];
+            let done_at = now + self.compile_times[coder_idx];
+            // a lone coder in a ring needs his only dongle twice, he never gets it
+            let needed_twice = (1..needs.len()).any(|nth| needs[..nth].contains(&needs[nth]));
+            let grantable = !needed_twice
+                && needs.iter().all(|dongle_idx| {
+                    let slot = &self.dongles[*dongle_idx];
+                    slot.held_until.is_none()
+                        && slot.ready_at.is_none_or(|ready_at| ready_at <= now)
+                        && reserved
+                            .get(dongle_idx)
+                            .is_none_or(|&from| done_at + slot.cooldown.mean() <= from)
+                });
+
+            if grantable {
+                // popped when he's first, so schedulers see their turns go by
+                if self.scheduling.peek() == Some(coder_id) {
+                    self.scheduling.pop();
+                } else {
+                    self.scheduling.remove(coder_id);
+                }
+                let since = self.asked[coder_idx].take().unwrap_or(now);
+                for &dongle_idx in needs {
+                    let slot = &mut self.dongles[dongle_idx];
+                    slot.holder = Some(coder_idx as u32 + 1);
+                    slot.held_until = Some(done_at);
+                    slot.next_cooldown = slot.cooldown.sample(&mut slot.rng);
+                    slot.usage.record_hold(now - since, now);
+                }
+                granted.push(coder_idx);
+            } else if !needed_twice {
+                let available_at = needs
+                    .iter()
+                    .map(|&dongle_idx| self.dongles[dongle_idx].available_at(now))
+                    .max()
+                    .unwrap_or(now);
+                for &dongle_idx in needs {
+                    let from = reserved.entry(dongle_idx).or_insert(available_at);
+                    *from = (*from).min(available_at);
+                }
+            }
+        }
+        granted
+    }
+
+    /// End of the next cooldown a waiting coder might be blocked on
+    fn next_ready(&self, now: Instant) -> Option<Instant> {
+        // nobody waits
+        self.scheduling.peek()?;
+        self.dongles
+            .iter()
+            .filter(|slot| slot.held_until.is_none())
+            .filter_map(|slot| slot.ready_at)
+            .filter(|&ready_at| ready_at > now)
+            .min()
+    }
+}
+
+impl Slot {
+    /// When it should be free and cooled down
+    fn available_at(&self, now: Instant) -> Instant {
+        let ready_at = match self.held_until {
+            Some(held_until) => held_until + self.next_cooldown,
+            None => self.ready_at.unwrap_or(now),
+        };
+        ready_at.max(now)
+    }
+}
+
+impl Seat {
+    /// Wait until the waiter grants every dongle, `false` if the run stopped first
+    pub fn acquire(&self, last_compile_time: Instant) -> bool {
+        let grants = self.grants.lock().unwrap();
+        let sent = self.waiter.outbox.send(Message::Ask {
+            coder_idx: self.coder_idx,
+            last_compile_time,
+            at: Instant::now(),
+        });
+
+        sent.is_ok() && grants.recv().unwrap_or(false)
+    }
+
+    pub fn cooldowns(&self) -> Vec<Duration> {
+        self.waiter.cooldowns(self.coder_idx)
+    }
+
+    pub fn release(&self, now: Instant) {
+        let _ = self.waiter.outbox.send(Message::Release {
+            coder_idx: self.coder_idx,
+            at: now,
+        });
+    }
+
+    pub fn stop(&self) {
+        self.waiter.stop();
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    sync::{
+        Mutex,
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    args::Args,
+    codexion::{dongle::DongleUsage, stats::DongleStatistics},
+    distribution::Distribution,
+    logging::Logging,
+    rng::Rng,
+    topology::Graph,
+};
+
+/// A coder's end of the Chandy–Misra protocol.
+///
+/// Every dongle is shared by at most two coders and is always on one side:
+/// either held by a coder, or on its way to him. The other side holds the
+/// request token, which it sends over to ask for the dongle. A coder gives
+/// up a requested dongle only when it's dirty, which is once he compiled with
+/// it, so both neighbours get their turn. Nothing is shared between threads
+/// but the channels.
+pub struct Diner {
+    coder_id: u32,
+    /// index of each fork's dongle
+    dongles: Vec<usize>,
+    state: Mutex<DinerState>,
+    /// his own inbox, to tell him the run is over
+    inbox: Sender<Message>,
+    /// per fork, kept apart from the state so statistics don't wait on him
+    usage: Mutex<Vec<DongleUsage>>,
+}
+
+struct DinerState {
+    forks: Vec<Fork>,
+    inbox: Receiver<Message>,
+    /// draws the cooldowns he starts
+    rng: Rng,
+    /// wants to compile, and asks for the dongles he doesn't hold
+    hungry: bool,
+    /// compiling, requests wait until he's done
+    compiling: bool,
+    stopped: bool,
+}
+
+/// His side of one of the dongles he needs
+struct Fork {
+    dongle_idx: usize,
+    cooldown: Distribution,
+    /// the other coder needing it, `None` when it's only his
+    neighbour: Option<Sender<Message>>,
+    held: bool,
+    /// used since he got it, so he gives it up when asked
+    dirty: bool,
+    /// he can ask for it when he doesn't hold it, he was asked for it when he does
+    token: bool,
+    /// end of the cooldown of its last use
+    ready_at: Option<Instant>,
+    /// when it arrived, while hungry
+    arrived_at: Option<Instant>,
+}
+
+enum Message {
+    /// the neighbour wants the dongle
+    Request { dongle_idx: usize },
+    /// the dongle itself, usable once its cooldown is over
+    Dongle {
+        dongle_idx: usize,
+        ready_at: Option<Instant>,
+    },
+    /// the run is over
+    Stop,
+}
+
+/// Seat every coder of the graph, connected to the neighbours he shares a
+/// dongle with. [`crate::ArgsBuilder::build`] checks no dongle has more than two
+pub fn seat(graph: &Graph, args: &Args) -> Vec<Diner> {
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        graph.coders.iter().map(|_| mpsc::channel()).unzip();
+
+    let mut sharers = vec![Vec::new(); graph.number_of_dongles as usize];
+    for (idx, dongles) in graph.coders.iter().enumerate() {
+        for &dongle_idx in dongles {
+            if !sharers[dongle_idx].contains(&idx) {
+                sharers[dongle_idx].push(idx);
+            }
+        }
+    }
+
+    receivers
+        .into_iter()
+        .enumerate()
+        .map(|(idx, inbox)| {
+            let dongles = &graph.coders[idx];
+            let forks: Vec<Fork> = dongles
+                .iter()
+                .enumerate()
+                .map(|(nth, &dongle_idx)| {
+                    // a lone coder in a ring needs his only dongle twice, he never holds it twice
+                    let needed_twice = dongles[..nth].contains(&dongle_idx);
+                    let neighbour = sharers[dongle_idx]
+                        .iter()
+                        .copied()
+                        .find(|&other| other != idx);
+                    // the lower index starts with it, so the precedence graph has no cycle
+                    let held = !needed_twice && neighbour.is_none_or(|other| idx < other);
+
+                    Fork {
+                        dongle_idx,
+                        cooldown: args.cooldown_of(dongle_idx as u32 + 1),
+                        neighbour: neighbour.map(|other| senders[other].clone()),
+                        held,
+                        dirty: true,
+                        token: !held && neighbour.is_some(),
+                        ready_at: None,
+                        arrived_at: None,
+                    }
+                })
+                .collect();
+
+            Diner {
+                coder_id: idx as u32 + 1,
+                dongles: dongles.clone(),
+                usage: Mutex::new(forks.iter().map(|_| DongleUsage::default()).collect()),
+                state: Mutex::new(DinerState {
+                    forks,
+                    inbox,
+                    // both sharers start cooldowns of a dongle, each from his own stream
+                    rng: Rng::stream(args.seed, (1 << 63) | idx as u64),
+                    hungry: false,
+                    compiling: false,
+                    stopped: false,
+                }),
+                inbox: senders[idx].clone(),
+            }
+        })
+        .collect()
+}
+
+impl Diner {
+    /// Get every dongle he needs and log them, `false` if the run stopped first
+    pub fn acquire_all(&self, logging: &Logging) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let since = Instant::now();
+        state.hungry = true;
+        state.request_missing();
+
+        loop {
+            if state.stopped {
+                return false;
+            }
+            if state.forks.iter().all(|fork| fork.held) {
+                let ready_at = state.forks.iter().filter_map(|fork| fork.ready_at).max();
+                match ready_at {
+                    Some(ready_at) if Instant::now() < ready_at => state.serve_until(ready_at),
+                    _ => break,
+                }
+            } else {
+                state.serve_one();
+            }
+        }
+
+        let now = Instant::now();
+        state.hungry = false;
+        state.compiling = true;
+        let mut usage = self.usage.lock().unwrap();
+        for (fork, usage) in state.forks.iter_mut().zip(usage.iter_mut()) {
+            // waited until it was there and cooled down
+            let usable_at = fork
+                .arrived_at
+                .take()
+                .unwrap_or(since)
+                .max(fork.ready_at.unwrap_or(since))
+                .max(since);
+            usage.record_hold(usable_at - since, now);
+            logging.acquire(self.coder_id, fork.dongle_idx as u32 + 1);
+        }
+        true
+    }
+
+    /// Log the releases, start the cooldowns and hand the dongles to the
+    /// neighbours who asked while he was compiling
+    pub fn release_all(&self, logging: &Logging) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let mut usage = self.usage.lock().unwrap();
+
+        for (fork, usage) in state.forks.iter_mut().zip(usage.iter_mut()).rev() {
+            let cooldown = fork.cooldown.sample(&mut state.rng);
+            logging.release(self.coder_id, fork.dongle_idx as u32 + 1, cooldown);
+            // after the release line, so the cooldown can't end before it
+            let now = Instant::now();
+            usage.record_release(now, cooldown);
+            fork.dirty = true;
+            fork.ready_at = Some(now + cooldown);
+        }
+        state.compiling = false;
+
+        for nth in 0..state.forks.len() {
+            state.answer(nth);
+        }
+    }
+
+    /// Answer the neighbours for `duration`, `false` if the run stopped
+    pub fn serve_for(&self, duration: Duration) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let deadline = Instant::now() + duration;
+
+        while !state.stopped && Instant::now() < deadline {
+            state.serve_until(deadline);
+        }
+        !state.stopped
+    }
+
+    /// Keep handing dongles to the neighbours once he's done, until the run stops
+    pub fn serve_until_stopped(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        while !state.stopped {
+            state.serve_one();
+        }
+    }
+
+    pub fn stop(&self) {
+        let _ = self.inbox.send(Message::Stop);
+    }
+}
+
+impl DinerState {
+    /// Wait for one message and handle it
+    fn serve_one(&mut self) {
+        // he holds a sender to his own inbox, it can't disconnect
+        if let Ok(message) = self.inbox.recv() {
+            self.handle(message);
+        }
+    }
+
+    /// Handle the messages that come until `deadline`
+    fn serve_until(&mut self, deadline: Instant) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.inbox.recv_timeout(timeout) {
+            Ok(message) => self.handle(message),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => (),
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Request { dongle_idx } => {
+                if let Some(nth) = self.fork_position(dongle_idx) {
+                    self.forks[nth].token = true;
+                    self.answer(nth);
+                }
+            }
+            Message::Dongle {
+                dongle_idx,
+                ready_at,
+            } => {
+                if let Some(nth) = self.fork_position(dongle_idx) {
+                    let fork = &mut self.forks[nth];
+                    fork.held = true;
+                    fork.dirty = false;
+                    fork.ready_at = ready_at;
+                    fork.arrived_at = Some(Instant::now());
+                }
+            }
+            Message::Stop => self.stopped = true,
+        }
+    }
+
+    /// The fork he shares with a neighbour for this dongle
+    fn fork_position(&self, dongle_idx: usize) -> Option<usize> {
+        self.forks
+            .iter()
+            .position(|fork| fork.dongle_idx == dongle_idx && fork.neighbour.is_some())
+    }
+
+    /// Give the dongle up if it was asked for and is dirty, and ask it back if hungry
+    fn answer(&mut self, nth: usize) {
+        let compiling = self.compiling;
+        let fork = &mut self.forks[nth];
+        let Some(neighbour) = &fork.neighbour else {
+            return;
+        };
+
+        if fork.held && fork.dirty && fork.token && !compiling {
+            fork.held = false;
+            fork.dirty = false;
+            // a neighbour who left doesn't need it anymore
+            let _ = neighbour.send(Message::Dongle {
+                dongle_idx: fork.dongle_idx,
+                ready_at: fork.ready_at,
+            });
+            if self.hungry {
+                self.request(nth);
+            }
+        }
+    }
+
+    fn request_missing(&mut self) {
+        for nth in 0..self.forks.len() {
+            self.request(nth);
+        }
+    }
+
+    /// Send the token for a dongle he doesn't hold
+    fn request(&mut self, nth: usize) {
+        let fork = &mut self.forks[nth];
+        if let Some(neighbour) = &fork.neighbour
+            && !fork.held
+            && fork.token
+        {
+            fork.token = false;
+            let _ = neighbour.send(Message::Request {
+                dongle_idx: fork.dongle_idx,
+            });
+        }
+    }
+}
+
+/// Per dongle summary of what both coders sharing it saw
+pub fn dongle_statistics<'a>(
+    diners: impl Iterator<Item = &'a Diner>,
+    number_of_dongles: u32,
+    start: Instant,
+    end: Instant,
+) -> Vec<DongleStatistics> {
+    let mut usages: Vec<DongleUsage> = (0..number_of_dongles)
+        .map(|_| DongleUsage::default())
+        .collect();
+
+    for diner in diners {
+        let usage = diner.usage.lock().unwrap();
+        for (&dongle_idx, usage) in diner.dongles.iter().zip(usage.iter()) {
+            usages[dongle_idx].merge(usage);
+        }
+    }
+
+    usages
+        .iter()
+        .enumerate()
+        .map(|(idx, usage)| usage.statistics(idx as u32 + 1, start, end))
+        .collect()
+}
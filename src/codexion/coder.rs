@@ -1,22 +1,62 @@
 use std::{
-    sync::{Arc, Condvar, Mutex},
-    thread::{self, JoinHandle, sleep},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     time::{Duration, Instant},
 };
 
 use crate::{
-    args::Args,
-    codexion::{Signal, dongle::Dongle},
+    args::CoderProfile,
+    codexion::{
+        Signal, arbiter::Seat, chandy_misra::Diner, dongle::Dongle, stats::CoderStatistics,
+    },
+    distribution::Distribution,
     logging::Logging,
+    rng::Rng,
 };
 
+/// How a coder gets his dongles
+pub enum DongleAccess {
+    /// shared dongles, in the global acquiring order
+    Locks(Vec<Arc<Dongle>>),
+    /// dongles passed around with his neighbours
+    Messages(Diner),
+    /// all of his dongles at once, from the arbiter
+    Arbiter(Seat),
+}
+
+/// What a coder is doing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoderPhase {
+    Starting,
+    /// waiting for one of his dongles, or for all of them at once when `None`
+    Acquiring {
+        dongle_id: Option<u32>,
+    },
+    Compiling,
+    Debugging,
+    Refactoring,
+    /// did all his compiles
+    Done,
+    /// left the run before his compiles, with the `kill` command
+    Killed,
+}
+
 pub struct Coder {
-    args: Args,
     pub id: u32,
+    pub profile: CoderProfile,
     pub compile_count: Mutex<u32>,
     pub last_compile_time: Mutex<Instant>,
-    first_dongle: Arc<Dongle>,
-    second_dongle: Arc<Dongle>,
+    pub longest_compile_gap: Mutex<Duration>,
+    phase: Mutex<CoderPhase>,
+    /// draws how long each of his phases lasts
+    rng: Mutex<Rng>,
+    /// every duration drawn is multiplied by it, set with the `slow` command
+    slowdown: Mutex<f64>,
+    /// set with the `kill` command, he leaves at the first chance
+    killed: AtomicBool,
+    dongles: DongleAccess,
     start_signal: Arc<Signal>,
     stop_signal: Arc<Signal>,
     logging: Arc<Logging>,
@@ -25,20 +65,24 @@ pub struct Coder {
 impl Coder {
     pub fn new(
         id: u32,
-        args: Args,
-        first_dongle: Arc<Dongle>,
-        second_dongle: Arc<Dongle>,
+        profile: CoderProfile,
+        seed: u64,
+        dongles: DongleAccess,
         start_signal: Arc<Signal>,
         stop_signal: Arc<Signal>,
         logging: Arc<Logging>,
     ) -> Self {
         Self {
-            args,
             id,
+            profile,
             compile_count: Mutex::new(0),
             last_compile_time: Mutex::new(Instant::now()),
-            first_dongle,
-            second_dongle,
+            longest_compile_gap: Mutex::new(Duration::ZERO),
+            phase: Mutex::new(CoderPhase::Starting),
+            rng: Mutex::new(Rng::for_coder(seed, id)),
+            slowdown: Mutex::new(1.0),
+            killed: AtomicBool::new(false),
+            dongles,
             start_signal,
             stop_signal,
             logging,
@@ -47,12 +91,7 @@ impl Coder {
 
     pub fn start_routine(&self) {
         // wait until the main thread signals start
-        {
-            let mut start_guard = self.start_signal.state.lock().unwrap();
-            while !*start_guard {
-                start_guard = self.start_signal.cond.wait(start_guard).unwrap();
-            }
-        }
+        self.start_signal.wait();
 
         // make the latest compile time now
         {
@@ -60,81 +99,255 @@ impl Coder {
             *last_compile_time = Instant::now();
         }
 
-        for _ in 0..self.args.number_of_compiles_required {
+        for _ in 0..self.profile.number_of_compiles_required {
             for action in [Coder::compile, Coder::debug, Coder::refactor] {
+                // a paused run holds him between two actions too
+                self.start_signal.wait();
                 action(self);
 
                 let should_stop = *self.stop_signal.state.lock().unwrap();
                 if should_stop {
                     return;
                 }
+                if self.is_killed() {
+                    self.set_phase(CoderPhase::Killed);
+                    return;
+                }
             }
         }
+
+        self.set_phase(CoderPhase::Done);
+        // his neighbours still need the dongles he holds
+        if let Some(diner) = self.diner() {
+            diner.serve_until_stopped();
+        }
+    }
+
+    pub fn phase(&self) -> CoderPhase {
+        *self.phase.lock().unwrap()
+    }
+
+    pub fn set_phase(&self, phase: CoderPhase) {
+        *self.phase.lock().unwrap() = phase;
+    }
+
+    /// How long his next compile lasts
+    pub fn next_time_to_compile(&self) -> Duration {
+        self.draw(self.profile.time_to_compile)
+    }
+
+    pub fn next_time_to_debug(&self) -> Duration {
+        self.draw(self.profile.time_to_debug)
+    }
+
+    pub fn next_time_to_refactor(&self) -> Duration {
+        self.draw(self.profile.time_to_refactor)
+    }
+
+    fn draw(&self, distribution: Distribution) -> Duration {
+        let duration = distribution.sample(&mut self.rng.lock().unwrap());
+        duration.mul_f64(*self.slowdown.lock().unwrap())
+    }
+
+    /// Make each of his next phases last `factor` times as long
+    pub fn slow_down(&self, factor: f64) {
+        *self.slowdown.lock().unwrap() = factor;
+    }
+
+    /// Make him leave the run: he gives up his dongles and stops compiling
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Relaxed)
+    }
+
+    /// Push his last compile `pause` later, as if the paused time never
+    /// happened, but not past `now`
+    pub fn shift(&self, pause: Duration, now: Instant) {
+        let mut last_compile_time = self.last_compile_time.lock().unwrap();
+        *last_compile_time = (*last_compile_time + pause).min(now);
+    }
+
+    pub fn diner(&self) -> Option<&Diner> {
+        match &self.dongles {
+            DongleAccess::Messages(diner) => Some(diner),
+            DongleAccess::Locks(_) | DongleAccess::Arbiter(_) => None,
+        }
+    }
+
+    /// Wake him up if he's waiting, so he notices the stop signal
+    pub fn interrupt(&self) {
+        match &self.dongles {
+            DongleAccess::Locks(_) => (),
+            DongleAccess::Messages(diner) => diner.stop(),
+            DongleAccess::Arbiter(seat) => seat.stop(),
+        }
     }
 
     fn compile(&self) {
+        match &self.dongles {
+            DongleAccess::Locks(dongles) => self.compile_with_locks(dongles),
+            DongleAccess::Messages(diner) => self.compile_with_messages(diner),
+            DongleAccess::Arbiter(seat) => self.compile_with_arbiter(seat),
+        }
+    }
+
+    fn compile_with_locks(&self, dongles: &[Arc<Dongle>]) {
         {
-            // acquire first dongle
-            let first_dongle_guard = self
-                .first_dongle
-                .acquire(self.id, self.get_last_compile_time());
-            if first_dongle_guard.is_none() {
-                return;
-            }
-            self.logging.acquire(self.id, 1);
-            // acquire second dongle
-            let second_dongle_guard = self
-                .second_dongle
-                .acquire(self.id, self.get_last_compile_time());
-            if second_dongle_guard.is_none() {
-                return;
+            // acquire every dongle, in order
+            let mut guards = Vec::with_capacity(dongles.len());
+            for dongle in dongles {
+                self.set_phase(CoderPhase::Acquiring {
+                    dongle_id: Some(dongle.id),
+                });
+                let acquired = dongle.acquire(self.id, self.get_last_compile_time(), &self.killed);
+                let Some(guard) = acquired else {
+                    return;
+                };
+                self.logging.acquire(self.id, dongle.id);
+                guards.push(guard);
             }
-            self.logging.acquire(self.id, 2);
 
             // compile
-            self.logging.compile(self.id);
-            let timedout = self.sleep(self.args.time_to_compile);
+            self.set_phase(CoderPhase::Compiling);
+            let time_to_compile = self.next_time_to_compile();
+            self.logging.compile(self.id, time_to_compile);
+            let timedout = self.sleep(time_to_compile);
 
             if !timedout {
                 return;
             }
+            // release and log the dongles, the last acquired first
+            guards.into_iter().rev().for_each(drop);
+        }
 
-            self.logging.release(self.id, 1);
-            self.logging.release(self.id, 2);
+        self.record_compile(Instant::now());
+    }
+
+    fn compile_with_messages(&self, diner: &Diner) {
+        self.set_phase(CoderPhase::Acquiring { dongle_id: None });
+        if !diner.acquire_all(&self.logging) {
+            return;
         }
 
-        // update latest compile time to now
+        self.set_phase(CoderPhase::Compiling);
+        let time_to_compile = self.next_time_to_compile();
+        self.logging.compile(self.id, time_to_compile);
+        if !self.sleep(time_to_compile) {
+            return;
+        }
+        diner.release_all(&self.logging);
+
+        self.record_compile(Instant::now());
+    }
+
+    /// Update latest compile time, compile count and the longest gap between compiles
+    pub fn record_compile(&self, now: Instant) {
         {
             let mut last_compile_time = self.last_compile_time.lock().unwrap();
-            *last_compile_time = Instant::now();
+            let mut longest_compile_gap = self.longest_compile_gap.lock().unwrap();
+            *longest_compile_gap = (*longest_compile_gap).max(now - *last_compile_time);
+            *last_compile_time = now;
         }
-        // update compile count
         {
             let mut compile_count = self.compile_count.lock().unwrap();
             *compile_count += 1;
         }
     }
 
+    pub fn statistics(&self, end: Instant) -> CoderStatistics {
+        let compiles = *self.compile_count.lock().unwrap();
+        let mut longest_gap = *self.longest_compile_gap.lock().unwrap();
+        // a coder who still had compiles to do was waiting until the end
+        if compiles < self.profile.number_of_compiles_required {
+            let last_compile_time = *self.last_compile_time.lock().unwrap();
+            longest_gap = longest_gap.max(end.saturating_duration_since(last_compile_time));
+        }
+
+        CoderStatistics {
+            id: self.id,
+            compiles,
+            longest_gap,
+        }
+    }
+
     fn debug(&self) {
-        self.logging.debug(self.id);
-        self.sleep(self.args.time_to_debug);
+        self.set_phase(CoderPhase::Debugging);
+        let time_to_debug = self.next_time_to_debug();
+        self.logging.debug(self.id, time_to_debug);
+        self.sleep(time_to_debug);
     }
 
     fn refactor(&self) {
-        self.logging.refactor(self.id);
-        self.sleep(self.args.time_to_refactor);
+        self.set_phase(CoderPhase::Refactoring);
+        let time_to_refactor = self.next_time_to_refactor();
+        self.logging.refactor(self.id, time_to_refactor);
+        self.sleep(time_to_refactor);
     }
 
+    fn compile_with_arbiter(&self, seat: &Seat) {
+        self.set_phase(CoderPhase::Acquiring { dongle_id: None });
+        if !seat.acquire(self.get_last_compile_time()) {
+            return;
+        }
+        for &dongle_id in &seat.dongle_ids {
+            self.logging.acquire(self.id, dongle_id);
+        }
+
+        self.set_phase(CoderPhase::Compiling);
+        let time_to_compile = self.next_time_to_compile();
+        self.logging.compile(self.id, time_to_compile);
+        if !self.sleep(time_to_compile) {
+            return;
+        }
+        let cooldowns = seat.cooldowns();
+        for (&dongle_id, &cooldown) in seat.dongle_ids.iter().zip(&cooldowns).rev() {
+            self.logging.release(self.id, dongle_id, cooldown);
+        }
+        // after the release lines, so the cooldowns can't end before them
+        let now = Instant::now();
+        seat.release(now);
+
+        self.record_compile(now);
+    }
+
+    /// Wait for `duration` without counting pauses, `false` if the run
+    /// stopped or he was killed first
     fn sleep(&self, duration: Duration) -> bool {
-        let stop_guard = self.stop_signal.state.lock().unwrap();
+        if let Some(diner) = self.diner() {
+            return diner.serve_for(duration);
+        }
+
+        let mut left = duration;
+        let mut stop_guard = self.stop_signal.state.lock().unwrap();
 
-        let (_guard, timeout) = self
-            .stop_signal
-            .cond
-            .wait_timeout(stop_guard, duration)
-            .unwrap();
+        loop {
+            if *stop_guard || self.is_killed() {
+                return false;
+            }
+            // the rest of it waits for the resume
+            if !self.start_signal.is_set() {
+                drop(stop_guard);
+                self.start_signal.wait();
+                stop_guard = self.stop_signal.state.lock().unwrap();
+                continue;
+            }
 
-        timeout.timed_out()
+            let since = Instant::now();
+            let timeout;
+            (stop_guard, timeout) = self
+                .stop_signal
+                .cond
+                .wait_timeout(stop_guard, left)
+                .unwrap();
+            if timeout.timed_out() {
+                return true;
+            }
+            // woken up by a pause or a kill, maybe someone else's
+            left = left.saturating_sub(since.elapsed());
+        }
     }
 
     fn get_last_compile_time(&self) -> Instant {
@@ -0,0 +1,183 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::{
+    clock::millis,
+    codexion::{
+        SimulationOutcome, coder::CoderPhase, dongle::DongleSnapshot, post_mortem::CoderSnapshot,
+    },
+};
+
+/// Time between two redraws
+pub const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Clear the screen and hide the cursor, before the first frame
+pub const ENTER: &str = "\x1b[2J\x1b[?25l";
+/// Show the cursor again, after the last frame
+pub const LEAVE: &str = "\x1b[?25h";
+
+const HOME: &str = "\x1b[H";
+const CLEAR_LINE_END: &str = "\x1b[K";
+const CLEAR_SCREEN_END: &str = "\x1b[J";
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+
+const FG_RED: &str = "\x1b[31m";
+const FG_GREEN: &str = "\x1b[32m";
+const FG_YELLOW: &str = "\x1b[33m";
+const FG_BLUE: &str = "\x1b[34m";
+const FG_MAGENTA: &str = "\x1b[35m";
+const FG_CYAN: &str = "\x1b[36m";
+
+/// Width of the countdown bars, in characters
+const BAR_WIDTH: usize = 20;
+
+/// One redraw of the dashboard
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// time since the start of the run
+    pub elapsed: Duration,
+    pub time_to_burnout: Duration,
+    pub coders: Vec<CoderSnapshot>,
+    /// empty when the coders pass the dongles around themselves
+    pub dongles: Vec<DongleSnapshot>,
+    /// how the run ended, on the last frame
+    pub outcome: Option<SimulationOutcome>,
+}
+
+fn phase_color(phase: CoderPhase) -> &'static str {
+    match phase {
+        CoderPhase::Starting => DIM,
+        CoderPhase::Acquiring { .. } => FG_BLUE,
+        CoderPhase::Compiling => FG_CYAN,
+        CoderPhase::Debugging => FG_YELLOW,
+        CoderPhase::Refactoring => FG_MAGENTA,
+        CoderPhase::Done => FG_GREEN,
+        CoderPhase::Killed => FG_RED,
+    }
+}
+
+impl Frame {
+    /// What's left before the coder burns out, as a bar emptying from the right
+    fn countdown(&self, coder: &CoderSnapshot) -> String {
+        if coder.compiles >= coder.compiles_required || coder.phase == CoderPhase::Killed {
+            return format!("{}{DIM}{:>9}{RESET}", " ".repeat(BAR_WIDTH + 2), "-");
+        }
+
+        let left = self
+            .time_to_burnout
+            .saturating_sub(coder.since_last_compile);
+        let share = left.as_secs_f64() / self.time_to_burnout.as_secs_f64();
+        let filled = ((share * BAR_WIDTH as f64).ceil() as usize).min(BAR_WIDTH);
+        let color = match share {
+            share if share > 0.5 => FG_GREEN,
+            share if share > 0.25 => FG_YELLOW,
+            _ => FG_RED,
+        };
+
+        format!(
+            "[{color}{}{RESET}{DIM}{}{RESET}] {:>6.0} ms",
+            "█".repeat(filled),
+            "·".repeat(BAR_WIDTH - filled),
+            millis(left)
+        )
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let done = self
+            .coders
+            .iter()
+            .filter(|coder| coder.compiles >= coder.compiles_required)
+            .count();
+        write!(f, "{HOME}")?;
+        writeln!(
+            f,
+            "{BOLD}codexion{RESET}  {:.0} ms  {done}/{} coders done  burnout after {:.0} ms{CLEAR_LINE_END}",
+            millis(self.elapsed),
+            self.coders.len(),
+            millis(self.time_to_burnout)
+        )?;
+
+        writeln!(f, "{CLEAR_LINE_END}")?;
+        writeln!(
+            f,
+            "{BOLD}{:>6}  {:<24} {:<32} {:>9}{RESET}{CLEAR_LINE_END}",
+            "coder", "phase", "until burnout", "compiles"
+        )?;
+        for coder in &self.coders {
+            writeln!(
+                f,
+                "{:>6}  {}{:<24}{RESET} {} {:>9}{CLEAR_LINE_END}",
+                coder.id,
+                phase_color(coder.phase),
+                coder.phase.to_string(),
+                self.countdown(coder),
+                format!("{}/{}", coder.compiles, coder.compiles_required)
+            )?;
+        }
+
+        writeln!(f, "{CLEAR_LINE_END}")?;
+        if self.dongles.is_empty() {
+            writeln!(
+                f,
+                "{DIM}dongles are passed between coders, there's no shared state to show{RESET}{CLEAR_LINE_END}"
+            )?;
+        } else {
+            writeln!(
+                f,
+                "{BOLD}{:>6}  {:<28} waiting line{RESET}{CLEAR_LINE_END}",
+                "dongle", "state"
+            )?;
+            for dongle in &self.dongles {
+                let state = match (dongle.holder, dongle.cooldown_left) {
+                    (Some(holder), _) => {
+                        format!("{FG_CYAN}{:<28}{RESET}", format!("held by coder {holder}"))
+                    }
+                    (None, Some(left)) => format!(
+                        "{FG_YELLOW}{:<28}{RESET}",
+                        format!("cooling down, {:.0} ms left", millis(left))
+                    ),
+                    (None, None) => format!("{FG_GREEN}{:<28}{RESET}", "free"),
+                };
+                let waiting: Vec<String> = dongle.waiting.iter().map(u32::to_string).collect();
+                writeln!(
+                    f,
+                    "{:>6}  {state} {}{CLEAR_LINE_END}",
+                    dongle.id,
+                    waiting.join(", ")
+                )?;
+            }
+        }
+
+        match self.outcome {
+            None => (),
+            Some(SimulationOutcome::Completed) => {
+                writeln!(f, "{CLEAR_LINE_END}")?;
+                writeln!(
+                    f,
+                    "{FG_GREEN}{BOLD}every coder did his compiles{RESET}{CLEAR_LINE_END}"
+                )?;
+            }
+            Some(SimulationOutcome::BurnedOut { coder_id, elapsed }) => {
+                writeln!(f, "{CLEAR_LINE_END}")?;
+                writeln!(
+                    f,
+                    "{FG_RED}{BOLD}coder {coder_id} burned out at {:.0} ms{RESET}{CLEAR_LINE_END}",
+                    millis(elapsed)
+                )?;
+            }
+            Some(SimulationOutcome::Interrupted { signal, elapsed }) => {
+                writeln!(f, "{CLEAR_LINE_END}")?;
+                writeln!(
+                    f,
+                    "{FG_YELLOW}{BOLD}interrupted by {signal} at {:.0} ms{RESET}{CLEAR_LINE_END}",
+                    millis(elapsed)
+                )?;
+            }
+        }
+        write!(f, "{CLEAR_SCREEN_END}")
+    }
+}
@@ -1,164 +1,466 @@
 use std::{
-    cmp::Reverse,
-    collections::{BinaryHeap, VecDeque},
-    sync::{Arc, Condvar, Mutex},
+    collections::BTreeMap,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     time::{Duration, Instant},
 };
 
 use crate::{
-    args::{Args, Scheduler},
-    codexion::Signal,
+    codexion::{
+        Signal,
+        scheduler::{Request, Scheduler},
+        stats::{DongleStatistics, WaitSummary},
+    },
+    distribution::Distribution,
+    logging::Logging,
+    rng::Rng,
 };
 
 pub struct Dongle {
-    cooldown: Duration,
+    pub id: u32,
     state: Mutex<DongleState>,
     pub release_cond: Condvar,
+    /// unset while the run is paused, nobody gets the dongle meanwhile
+    start_signal: Arc<Signal>,
     stop_signal: Arc<Signal>,
+    logging: Arc<Logging>,
 }
 
 struct DongleState {
     availability: DongleAvailability,
-    scheduling: SchedulingStrategy,
+    /// changed with the `cooldown` command
+    cooldown: Distribution,
+    scheduling: Box<dyn Scheduler>,
+    usage: DongleUsage,
+    /// draws the cooldowns
+    rng: Rng,
+    /// per coder in line, how many coders who came after him got it first
+    passed_over: BTreeMap<u32, u32>,
 }
 
-enum SchedulingStrategy {
-    Queue(VecDeque<u32>),
-    Heap(BinaryHeap<(Reverse<Instant>, u32)>),
+/// What the dongle went through, for the end of run statistics
+#[derive(Default)]
+pub(super) struct DongleUsage {
+    /// when each coder in the waiting line joined it
+    waiting_since: Vec<(u32, Instant)>,
+    waits: Vec<Duration>,
+    held: Duration,
+    held_since: Option<Instant>,
+    last_release: Option<Instant>,
+    /// every cooldown drawn, and the last one
+    cooldowns: Duration,
+    last_cooldown: Duration,
 }
 
 enum DongleAvailability {
     Available,
     CoolingDownUntil(Instant),
-    Held,
+    Held { coder_id: u32 },
 }
 
-pub struct DongleGuard<'a>(&'a Dongle);
+/// Who holds the dongle and who waits for it, at one instant
+#[derive(Clone, Debug)]
+pub struct DongleSnapshot {
+    pub id: u32,
+    pub holder: Option<u32>,
+    /// what's left of its cooldown, when it's not held
+    pub cooldown_left: Option<Duration>,
+    /// the next one first
+    pub waiting: Vec<u32>,
+    /// coders in line who were passed over, and how many times
+    pub passed_over: Vec<(u32, u32)>,
+}
 
-impl Dongle {
-    pub fn new(args: Args, stop_signal: Arc<Signal>) -> Self {
-        let scheduling = match args.scheduler {
-            Scheduler::FIFO => SchedulingStrategy::Queue(VecDeque::with_capacity(2)),
-            Scheduler::EDF => SchedulingStrategy::Heap(BinaryHeap::with_capacity(2)),
-        };
+/// Outcome of a single attempt at taking a dongle
+pub enum Acquisition {
+    Granted,
+    CoolingDownUntil(Instant),
+    /// held by someone else, or someone else is first in line
+    Busy,
+}
 
+pub struct DongleGuard<'a> {
+    dongle: &'a Dongle,
+    coder_id: u32,
+}
+
+impl Dongle {
+    pub fn new(
+        id: u32,
+        cooldown: Distribution,
+        seed: u64,
+        scheduling: Box<dyn Scheduler>,
+        start_signal: Arc<Signal>,
+        stop_signal: Arc<Signal>,
+        logging: Arc<Logging>,
+    ) -> Self {
         Self {
-            cooldown: args.dongle_cooldown,
+            id,
             state: Mutex::new(DongleState {
                 availability: DongleAvailability::Available,
+                cooldown,
                 scheduling,
+                usage: DongleUsage::default(),
+                rng: Rng::for_dongle(seed, id),
+                passed_over: BTreeMap::new(),
             }),
             release_cond: Condvar::new(),
+            start_signal,
             stop_signal,
+            logging,
         }
     }
 
+    /// Wait in line until the coder gets the dongle, `None` if the run
+    /// stopped or he was `killed` first
     pub fn acquire<'a>(
         &'a self,
         coder_id: u32,
         last_compile_time: Instant,
+        killed: &AtomicBool,
     ) -> Option<DongleGuard<'a>> {
         let mut state = self.state.lock().unwrap();
 
-        Self::add_coder_to_waiting_line(&mut state.scheduling, coder_id, last_compile_time);
+        state.join_line(coder_id, last_compile_time, Instant::now());
 
         loop {
             // check whether a stop signal was sent by the monitor
-            if *self.stop_signal.state.lock().unwrap() {
+            if *self.stop_signal.state.lock().unwrap() || killed.load(Ordering::Relaxed) {
+                state.scheduling.remove(coder_id);
+                state.usage.waiting_since.retain(|&(id, _)| id != coder_id);
+                state.passed_over.remove(&coder_id);
+                // whoever was behind him may be first now
+                self.release_cond.notify_all();
                 break None;
             }
+            // nothing is handed out while the run is paused
+            if !self.start_signal.is_set() {
+                drop(state);
+                self.start_signal.wait();
+                state = self.state.lock().unwrap();
+                continue;
+            }
 
-            match state.availability {
-                // if the dongle is available and the coder is first in line
-                // acquire it and pop out of the waiting line
-                DongleAvailability::Available => {
-                    if Self::try_pop_coder_from_line(&mut state.scheduling, coder_id) {
-                        state.availability = DongleAvailability::Held;
-                        break Some(DongleGuard(self));
-                    }
+            let now = Instant::now();
+            match state.try_take(coder_id, now) {
+                Acquisition::Granted => {
+                    break Some(DongleGuard {
+                        dongle: self,
+                        coder_id,
+                    });
                 }
-                // if the dongle is cooling down, wait for the rest of cooldown
-                // else, acquire if first in line and pop out
-                DongleAvailability::CoolingDownUntil(next_available) => {
-                    let now = Instant::now();
-
-                    if now >= next_available {
-                        if Self::try_pop_coder_from_line(&mut state.scheduling, coder_id) {
-                            state.availability = DongleAvailability::Held;
-                            break Some(DongleGuard(self));
-                        }
-                    } else {
-                        (state, _) = self
-                            .release_cond
-                            .wait_timeout(state, next_available - now)
-                            .unwrap();
-                    }
+                // wait for the rest of cooldown
+                Acquisition::CoolingDownUntil(next_available) => {
+                    (state, _) = self
+                        .release_cond
+                        .wait_timeout(state, next_available - now)
+                        .unwrap();
                 }
-                // if the dongle is held, wait for state change
-                DongleAvailability::Held => {
+                // wait for the holder to release it
+                Acquisition::Busy => {
                     state = self.release_cond.wait(state).unwrap();
                 }
             }
         }
     }
 
+    /// Put the coder in the waiting line without blocking, used by the virtual clock
+    pub fn join_line(&self, coder_id: u32, last_compile_time: Instant, now: Instant) {
+        self.state
+            .lock()
+            .unwrap()
+            .join_line(coder_id, last_compile_time, now);
+    }
+
+    /// Try to take the dongle at `now` without blocking, used by the virtual clock
+    pub fn try_take(&self, coder_id: u32, now: Instant) -> Acquisition {
+        self.state.lock().unwrap().try_take(coder_id, now)
+    }
+
     fn add_coder_to_waiting_line(
-        scheduling: &mut SchedulingStrategy,
+        scheduling: &mut dyn Scheduler,
         coder_id: u32,
         last_compile_time: Instant,
     ) {
-        match &mut *scheduling {
-            SchedulingStrategy::Queue(queue) => queue.push_front(coder_id),
-            SchedulingStrategy::Heap(heap) => heap.push((Reverse(last_compile_time), coder_id)),
-        }
+        scheduling.enqueue(Request {
+            coder_id,
+            last_compile_time,
+        });
     }
 
     /// Remove coder from waiting line, if he's first
-    fn try_pop_coder_from_line(scheduling: &mut SchedulingStrategy, coder_id: u32) -> bool {
-        match scheduling {
-            SchedulingStrategy::Queue(queue) => {
-                let next_id_in_line = queue.back();
-
-                if let Some(&next_id) = next_id_in_line
-                    && next_id == coder_id
-                {
-                    queue.pop_back();
-                    return true;
-                }
+    fn try_pop_coder_from_line(scheduling: &mut dyn Scheduler, coder_id: u32) -> bool {
+        scheduling.choose();
+        if scheduling.peek() == Some(coder_id) {
+            scheduling.pop();
+            return true;
+        }
+        false
+    }
+
+    /// Change the state to available and notify others who are waiting
+    pub fn release(&self, coder_id: u32) {
+        self.release_at(coder_id, Instant::now());
+    }
+
+    /// Start a cooldown at `now` and notify others who are waiting, gives
+    /// the cooldown drawn
+    pub fn release_at(&self, coder_id: u32, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+
+        let DongleAvailability::Held { .. } = state.availability else {
+            return Duration::ZERO;
+        };
+        let distribution = state.cooldown;
+        let cooldown = distribution.sample(&mut state.rng);
+        // logged under the state lock, so it comes before the next holder's acquire
+        self.logging.release(coder_id, self.id, cooldown);
+        state.availability = DongleAvailability::CoolingDownUntil(now + cooldown);
+        state.usage.record_release(now, cooldown);
+        self.release_cond.notify_all();
+        cooldown
+    }
+
+    /// Summary of the dongle's usage over a run from `start` to `end`
+    pub fn statistics(&self, start: Instant, end: Instant) -> DongleStatistics {
+        let state = self.state.lock().unwrap();
+        state.usage.statistics(self.id, start, end)
+    }
+
+    pub fn snapshot(&self, now: Instant) -> DongleSnapshot {
+        let state = self.state.lock().unwrap();
+        let (holder, cooldown_left) = match state.availability {
+            DongleAvailability::Held { coder_id } => (Some(coder_id), None),
+            DongleAvailability::CoolingDownUntil(ready_at) if now < ready_at => {
+                (None, Some(ready_at - now))
             }
+            DongleAvailability::Available | DongleAvailability::CoolingDownUntil(_) => (None, None),
+        };
 
-            SchedulingStrategy::Heap(heap) => {
-                let next_id_in_line = heap.peek();
+        DongleSnapshot {
+            id: self.id,
+            holder,
+            cooldown_left,
+            waiting: state.scheduling.waiting(),
+            passed_over: state
+                .passed_over
+                .iter()
+                .map(|(&coder_id, &count)| (coder_id, count))
+                .collect(),
+        }
+    }
 
-                if let Some(&(Reverse(_), next_id)) = next_id_in_line
-                    && next_id == coder_id
-                {
-                    heap.pop();
-                    return true;
+    /// Draw the next cooldowns from `cooldown`, the one going on is left as it is
+    pub fn set_cooldown(&self, cooldown: Distribution) {
+        self.state.lock().unwrap().cooldown = cooldown;
+    }
+
+    /// Push its cooldown and usage `pause` later, as if the paused time never
+    /// happened, but not past `now`
+    pub fn shift(&self, pause: Duration, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if let DongleAvailability::CoolingDownUntil(ready_at) = &mut state.availability {
+            *ready_at += pause;
+        }
+        state.usage.shift(pause, now);
+    }
+
+    /// Wake every coder waiting on this dongle, so they can notice a stop signal
+    pub fn wake_all(&self) {
+        let _state = self.state.lock().unwrap();
+        self.release_cond.notify_all();
+    }
+}
+
+impl DongleState {
+    fn join_line(&mut self, coder_id: u32, last_compile_time: Instant, now: Instant) {
+        Dongle::add_coder_to_waiting_line(self.scheduling.as_mut(), coder_id, last_compile_time);
+        self.usage.waiting_since.push((coder_id, now));
+    }
+
+    /// Take the dongle if it's free and the coder is first in line
+    fn try_take(&mut self, coder_id: u32, now: Instant) -> Acquisition {
+        match self.availability {
+            DongleAvailability::CoolingDownUntil(next_available) if now < next_available => {
+                Acquisition::CoolingDownUntil(next_available)
+            }
+            DongleAvailability::Available | DongleAvailability::CoolingDownUntil(_) => {
+                if Dongle::try_pop_coder_from_line(self.scheduling.as_mut(), coder_id) {
+                    self.availability = DongleAvailability::Held { coder_id };
+                    self.record_passed_over(coder_id);
+                    self.usage.record_grant(coder_id, now);
+                    Acquisition::Granted
+                } else {
+                    Acquisition::Busy
                 }
             }
+            DongleAvailability::Held { .. } => Acquisition::Busy,
         }
-        false
     }
 
-    /// Change the state to available and notify others who are waiting
-    pub fn release(&self) {
-        let mut state = self.state.lock().unwrap();
+    /// Count a turn lost by everyone who joined the line before `coder_id`
+    fn record_passed_over(&mut self, coder_id: u32) {
+        self.passed_over.remove(&coder_id);
+        let waiting_since = &self.usage.waiting_since;
+        let Some(&(_, joined)) = waiting_since.iter().find(|&&(id, _)| id == coder_id) else {
+            return;
+        };
 
-        match state.availability {
-            DongleAvailability::Held => {
-                state.availability =
-                    DongleAvailability::CoolingDownUntil(Instant::now() + self.cooldown);
-                self.release_cond.notify_all();
+        for &(id, since) in waiting_since {
+            if id != coder_id && since < joined {
+                *self.passed_over.entry(id).or_default() += 1;
+            }
+        }
+    }
+}
+
+impl DongleUsage {
+    /// Count a coder's wait and hold the dongle from `now`
+    pub(super) fn record_hold(&mut self, wait: Duration, now: Instant) {
+        self.waits.push(wait);
+        self.held_since = Some(now);
+    }
+
+    pub(super) fn record_release(&mut self, now: Instant, cooldown: Duration) {
+        if let Some(held_since) = self.held_since.take() {
+            self.held += now - held_since;
+        }
+        self.cooldowns += cooldown;
+        self.last_cooldown = cooldown;
+        self.last_release = Some(now);
+    }
+
+    /// Add what another coder saw of the same dongle
+    pub(super) fn merge(&mut self, other: &DongleUsage) {
+        self.waits.extend_from_slice(&other.waits);
+        self.held += other.held;
+        self.held_since = self.held_since.or(other.held_since);
+        self.cooldowns += other.cooldowns;
+        if other.last_release > self.last_release {
+            self.last_release = other.last_release;
+            self.last_cooldown = other.last_cooldown;
+        }
+    }
+
+    pub(super) fn statistics(&self, id: u32, start: Instant, end: Instant) -> DongleStatistics {
+        let run_duration = end.saturating_duration_since(start);
+
+        let held = self.held
+            + self.held_since.map_or(Duration::ZERO, |held_since| {
+                end.saturating_duration_since(held_since)
+            });
+        // every cooldown but the last one ran to completion before the next acquire
+        let cooled_down = match self.last_release {
+            Some(last_release) => {
+                self.cooldowns - self.last_cooldown
+                    + self
+                        .last_cooldown
+                        .min(end.saturating_duration_since(last_release))
             }
-            _ => (),
+            None => Duration::ZERO,
+        };
+
+        DongleStatistics {
+            id,
+            waits: WaitSummary::new(self.waits.clone()),
+            utilisation: if run_duration.is_zero() {
+                0.0
+            } else {
+                held.as_secs_f64() / run_duration.as_secs_f64()
+            },
+            cooldown: cooled_down,
         }
     }
+
+    fn shift(&mut self, pause: Duration, now: Instant) {
+        let shift = |instant: &mut Instant| *instant = (*instant + pause).min(now);
+        self.waiting_since
+            .iter_mut()
+            .for_each(|(_, since)| shift(since));
+        self.held_since.iter_mut().for_each(shift);
+        self.last_release.iter_mut().for_each(shift);
+    }
+
+    fn record_grant(&mut self, coder_id: u32, now: Instant) {
+        if let Some(position) = self
+            .waiting_since
+            .iter()
+            .position(|&(id, _)| id == coder_id)
+        {
+            let (_, since) = self.waiting_since.remove(position);
+            self.waits.push(now - since);
+        }
+        self.held_since = Some(now);
+    }
 }
 
 impl<'a> Drop for DongleGuard<'a> {
     /// Release the dongle when the guard drops
     fn drop(&mut self) {
-        self.0.release();
+        self.dongle.release(self.coder_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{args::LogFormat, clock::Clock, codexion::scheduler::Fifo};
+
+    fn dongle() -> Arc<Dongle> {
+        let start_signal = Arc::new(Signal::new());
+        start_signal.set(true);
+        Arc::new(Dongle::new(
+            1,
+            Duration::ZERO.into(),
+            0,
+            Box::new(Fifo::default()),
+            start_signal,
+            Arc::new(Signal::new()),
+            Arc::new(Logging::new(Arc::new(Clock::Real), LogFormat::Quiet, false)),
+        ))
+    }
+
+    fn wait_for_line(dongle: &Dongle, line: &[u32]) {
+        while dongle.snapshot(Instant::now()).waiting != line {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn killed_coder_at_the_head_of_the_line_wakes_the_next_one() {
+        let dongle = dongle();
+        let killed = Arc::new(AtomicBool::new(false));
+        dongle.state.lock().unwrap().availability = DongleAvailability::Held { coder_id: 1 };
+
+        let waiter = {
+            let dongle = Arc::clone(&dongle);
+            let killed = Arc::clone(&killed);
+            thread::spawn(move || dongle.acquire(2, Instant::now(), &killed).is_none())
+        };
+        wait_for_line(&dongle, &[2]);
+
+        // coder 3 waits behind him, the dongle frees up and coder 2 is
+        // killed before he wakes up to take it
+        let mut state = dongle.state.lock().unwrap();
+        state.join_line(3, Instant::now(), Instant::now());
+        state.availability = DongleAvailability::Available;
+        killed.store(true, Ordering::Relaxed);
+        dongle.release_cond.notify_all();
+        let (mut state, timeout) = dongle
+            .release_cond
+            .wait_timeout(state, Duration::from_secs(5))
+            .unwrap();
+
+        assert!(!timeout.timed_out(), "nobody woke coder 3 up");
+        assert!(matches!(
+            state.try_take(3, Instant::now()),
+            Acquisition::Granted
+        ));
+        drop(state);
+        assert!(waiter.join().unwrap());
     }
 }
@@ -0,0 +1,290 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io,
+    num::ParseIntError,
+    sync::{
+        atomic::Ordering,
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    args::ArgsError,
+    clock::millis,
+    codexion::{Codexion, SIGNAL_CHECK_INTERVAL, coder::Coder},
+    distribution::Distribution,
+};
+
+/// One line typed while the coders run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// hold every coder and dongle where they are, the clock stops with them
+    Pause,
+    Resume,
+    /// print what every coder and dongle is doing
+    Status,
+    /// make every phase of a coder last `factor` times as long
+    Slow {
+        coder_id: u32,
+        factor: f64,
+    },
+    /// make a coder leave, he gives his dongles back and compiles no more
+    Kill {
+        coder_id: u32,
+    },
+    /// draw the next cooldowns of a dongle from `cooldown`
+    Cooldown {
+        dongle_id: u32,
+        cooldown: Distribution,
+    },
+    /// stop the run like Ctrl-C
+    Stop,
+    Help,
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    UnknownCommand(String),
+    /// the command with what it takes
+    Usage(&'static str),
+    InvalidId {
+        value: String,
+        source: ParseIntError,
+    },
+    InvalidFactor(String),
+    InvalidCooldown(ArgsError),
+    UnknownCoder(u32),
+    UnknownDongle(u32),
+    AlreadyPaused,
+    NotPaused,
+}
+
+impl TryFrom<&str> for Command {
+    type Error = CommandError;
+
+    /// A command and its arguments, separated by spaces
+    fn try_from(line: &str) -> Result<Self, Self::Error> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let id = |value: &str| -> Result<u32, CommandError> {
+            value.parse().map_err(|source| CommandError::InvalidId {
+                value: value.to_string(),
+                source,
+            })
+        };
+
+        match words.as_slice() {
+            ["pause"] => Ok(Command::Pause),
+            ["resume"] => Ok(Command::Resume),
+            ["status"] => Ok(Command::Status),
+            ["slow", coder_id, factor] => {
+                let factor = factor
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|factor| factor.is_finite() && *factor > 0.0)
+                    .ok_or_else(|| CommandError::InvalidFactor(factor.to_string()))?;
+                Ok(Command::Slow {
+                    coder_id: id(coder_id)?,
+                    factor,
+                })
+            }
+            ["slow", ..] => Err(CommandError::Usage("slow <coder> <factor>")),
+            ["kill", coder_id] => Ok(Command::Kill {
+                coder_id: id(coder_id)?,
+            }),
+            ["kill", ..] => Err(CommandError::Usage("kill <coder>")),
+            ["cooldown", dongle_id, cooldown] => Ok(Command::Cooldown {
+                dongle_id: id(dongle_id)?,
+                cooldown: Distribution::parse(cooldown, "cooldown")
+                    .map_err(CommandError::InvalidCooldown)?,
+            }),
+            ["cooldown", ..] => Err(CommandError::Usage("cooldown <dongle> <ms>")),
+            ["stop"] => Ok(Command::Stop),
+            ["help"] => Ok(Command::Help),
+            _ => Err(CommandError::UnknownCommand(line.trim().to_string())),
+        }
+    }
+}
+
+/// Every line of stdin, read on a thread of its own: a read can't be
+/// interrupted, so it's left waiting for a line when the run is over
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+impl Codexion {
+    /// Run the commands typed on stdin until the run stops, replies go to
+    /// stderr to keep them out of the log
+    pub(super) fn control(&self) {
+        let lines = read_lines();
+        eprintln!("interactive: type help for the commands");
+
+        while !*self.stop_signal.state.lock().unwrap() {
+            match lines.recv_timeout(SIGNAL_CHECK_INTERVAL) {
+                Ok(line) if line.trim().is_empty() => (),
+                Ok(line) => {
+                    if let Err(error) =
+                        Command::try_from(line.as_str()).and_then(|command| self.execute(command))
+                    {
+                        eprintln!("{error}");
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                // nobody can resume it anymore
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Ok(pause) = self.resume() {
+                        eprintln!("stdin closed, resumed after {:.1} ms paused", millis(pause));
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    fn execute(&self, command: Command) -> Result<(), CommandError> {
+        match command {
+            Command::Pause => {
+                let elapsed = self.pause()?;
+                eprintln!("paused at {:.1} ms", millis(elapsed));
+            }
+            Command::Resume => {
+                let pause = self.resume()?;
+                eprintln!("resumed after {:.1} ms paused", millis(pause));
+            }
+            Command::Status => self.report_snapshot(),
+            Command::Slow { coder_id, factor } => {
+                self.coder(coder_id)?.slow_down(factor);
+                eprintln!("coder {coder_id} now takes {factor}x as long");
+            }
+            Command::Kill { coder_id } => {
+                self.coder(coder_id)?.kill();
+                self.wake_coders();
+                eprintln!("coder {coder_id} killed");
+            }
+            Command::Cooldown {
+                dongle_id,
+                cooldown,
+            } => {
+                let dongle = self
+                    .dongles
+                    .get((dongle_id as usize).wrapping_sub(1))
+                    .ok_or(CommandError::UnknownDongle(dongle_id))?;
+                dongle.set_cooldown(cooldown);
+                eprintln!(
+                    "dongle {dongle_id} now cools down for {:.1} ms{}",
+                    millis(cooldown.mean()),
+                    if cooldown.is_constant() {
+                        ""
+                    } else {
+                        " on average"
+                    }
+                );
+            }
+            Command::Stop => self.stop_command.store(true, Ordering::Relaxed),
+            Command::Help => eprintln!(
+                "pause | resume | status | slow <coder> <factor> | kill <coder> | cooldown <dongle> <ms> | stop"
+            ),
+        }
+        Ok(())
+    }
+
+    fn coder(&self, coder_id: u32) -> Result<&Coder, CommandError> {
+        self.coders
+            .get((coder_id as usize).wrapping_sub(1))
+            .map(|coder| coder.as_ref())
+            .ok_or(CommandError::UnknownCoder(coder_id))
+    }
+
+    /// Hold the coders where they are, gives the time since the start
+    fn pause(&self) -> Result<Duration, CommandError> {
+        let elapsed = self.elapsed();
+        {
+            let mut paused_at = self.paused_at.lock().unwrap();
+            if paused_at.is_some() {
+                return Err(CommandError::AlreadyPaused);
+            }
+            *paused_at = Some(Instant::now());
+        }
+        self.start_signal.set(false);
+        // the ones asleep wake up to see it
+        self.wake_coders();
+        Ok(elapsed)
+    }
+
+    /// Let the coders go on as if the pause never happened, gives how long it lasted
+    fn resume(&self) -> Result<Duration, CommandError> {
+        let mut paused_at = self.paused_at.lock().unwrap();
+        let since = paused_at.ok_or(CommandError::NotPaused)?;
+        let now = Instant::now();
+        let pause = now - since;
+
+        // the pause counts toward no burnout, cooldown or logged time
+        *self.logging.start_time_lock.lock().unwrap() += pause;
+        for coder in &self.coders {
+            coder.shift(pause, now);
+        }
+        for dongle in &self.dongles {
+            dongle.shift(pause, now);
+        }
+        *paused_at = None;
+        drop(paused_at);
+
+        self.start_signal.set(true);
+        Ok(pause)
+    }
+
+    /// Wake every coder asleep or in a waiting line, to notice a pause or a kill
+    fn wake_coders(&self) {
+        {
+            let _stop = self.stop_signal.state.lock().unwrap();
+            self.stop_signal.cond.notify_all();
+        }
+        for dongle in &self.dongles {
+            dongle.wake_all();
+        }
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::UnknownCommand(command) => write!(
+                f,
+                "Error: unknown command '{command}' (expected pause, resume, status, slow, kill, cooldown, stop or help)"
+            ),
+            CommandError::Usage(usage) => write!(f, "Error: usage: {usage}"),
+            CommandError::InvalidId { value, source } => {
+                write!(f, "Error: invalid id '{value}': {source}")
+            }
+            CommandError::InvalidFactor(value) => write!(
+                f,
+                "Error: invalid factor '{value}' (expected a number above 0)"
+            ),
+            CommandError::InvalidCooldown(source) => write!(f, "{source}"),
+            CommandError::UnknownCoder(coder_id) => {
+                write!(f, "Error: coder {coder_id} doesn't exist")
+            }
+            CommandError::UnknownDongle(dongle_id) => {
+                write!(f, "Error: dongle {dongle_id} doesn't exist")
+            }
+            CommandError::AlreadyPaused => write!(f, "Error: the run is already paused"),
+            CommandError::NotPaused => write!(f, "Error: the run isn't paused"),
+        }
+    }
+}
+
+impl Error for CommandError {}
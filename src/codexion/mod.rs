@@ -1,21 +1,79 @@
+mod arbiter;
+mod chandy_misra;
 mod coder;
+pub mod dashboard;
 mod dongle;
+mod interactive;
+pub mod post_mortem;
+pub mod scheduler;
+pub mod signals;
+mod simulation;
+pub mod stats;
+pub mod watchdog;
+use std::fs;
+use std::io::{Write, stdout};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, sleep};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::args::Args;
-use crate::codexion::coder::Coder;
+use crate::args::{Args, Backend, ClockMode, LogFormat};
+use crate::clock::{Clock, millis};
+use crate::codexion::arbiter::Waiter;
+use crate::codexion::coder::{Coder, DongleAccess};
+use crate::codexion::dashboard::Frame;
 use crate::codexion::dongle::Dongle;
-use crate::logging::Logging;
+use crate::codexion::post_mortem::{CoderSnapshot, PostMortem};
+use crate::codexion::scheduler::Scheduler;
+use crate::codexion::signals::StopSignal;
+use crate::codexion::simulation::Simulation;
+use crate::codexion::stats::Statistics;
+use crate::codexion::watchdog::{WaitForGraph, Watchdog};
+use crate::logging::{Logging, Observer};
+use crate::replay::{Recorder, Replayed};
+use crate::topology::Graph;
+use crate::trace::Trace;
+
+pub use crate::codexion::coder::CoderPhase;
+pub use crate::codexion::dongle::DongleSnapshot;
+
+/// Longest the monitor sleeps before it looks for a signal
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
 
 pub struct Codexion {
     args: Args,
+    graph: Graph,
     dongles: Vec<Arc<Dongle>>,
+    /// hands out the dongles with the arbiter backend
+    waiter: Option<Arc<Waiter>>,
     coders: Vec<Arc<Coder>>,
+    /// set while the coders run, unset again while the run is paused
     start_signal: Arc<Signal>,
     stop_signal: Arc<Signal>,
+    /// when the run was paused with the `pause` command
+    paused_at: Mutex<Option<Instant>>,
+    /// set by the `stop` command
+    stop_command: AtomicBool,
+    clock: Arc<Clock>,
     logging: Arc<Logging>,
+    /// records the run when `--trace-out` is given
+    trace: Option<Arc<Trace>>,
+    /// records the grants when `--record` is given
+    recorder: Option<Arc<Recorder>>,
+}
+
+/// How a run ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationOutcome {
+    /// every coder did all his required compiles
+    Completed,
+    /// a coder went `time_to_burnout` without compiling, `elapsed` after the start
+    BurnedOut { coder_id: u32, elapsed: Duration },
+    /// stopped by a signal, `elapsed` after the start
+    Interrupted {
+        signal: StopSignal,
+        elapsed: Duration,
+    },
 }
 
 struct Signal {
@@ -23,41 +81,134 @@ struct Signal {
     cond: Condvar,
 }
 
-impl Codexion {
-    pub fn new(args: Args) -> Self {
-        let start_signal = Arc::new(Signal {
+impl Signal {
+    fn new() -> Self {
+        Self {
             state: Mutex::new(false),
             cond: Condvar::new(),
+        }
+    }
+
+    /// Change the state and wake everyone waiting for it
+    fn set(&self, value: bool) {
+        *self.state.lock().unwrap() = value;
+        self.cond.notify_all();
+    }
+
+    fn is_set(&self) -> bool {
+        *self.state.lock().unwrap()
+    }
+
+    /// Block until the state is set
+    fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !*state {
+            state = self.cond.wait(state).unwrap();
+        }
+    }
+}
+
+impl Codexion {
+    pub fn new(args: Args) -> Self {
+        let scheduler_args = args.clone();
+        Self::with_scheduler(args, move |dongle_id| {
+            scheduler_args.scheduler.build(&scheduler_args, dongle_id)
+        })
+    }
+
+    /// Build a codexion where every dongle gets its waiting line from
+    /// `new_scheduler`, called with the dongle id, or 0 for the arbiter's
+    pub fn with_scheduler(args: Args, new_scheduler: impl Fn(u32) -> Box<dyn Scheduler>) -> Self {
+        let start_signal = Arc::new(Signal::new());
+        let stop_signal = Arc::new(Signal::new());
+
+        let clock = match args.clock {
+            ClockMode::Real => Clock::Real,
+            ClockMode::Virtual => Clock::new_virtual(Instant::now()),
+        };
+        let clock = Arc::new(clock);
+        let logging = Arc::new(Logging::new(
+            Arc::clone(&clock),
+            args.log_format,
+            args.random_durations(),
+        ));
+
+        let trace = args.trace_out.as_ref().map(|_| {
+            let trace = Arc::new(Trace::new(&args));
+            logging.add_observer(Arc::clone(&trace) as Arc<dyn Observer>);
+            trace
         });
-        let stop_signal = Arc::new(Signal {
-            state: Mutex::new(false),
-            cond: Condvar::new(),
+        let recorder = args.record.as_ref().map(|_| {
+            let recorder = Arc::new(Recorder::default());
+            logging.add_observer(Arc::clone(&recorder) as Arc<dyn Observer>);
+            recorder
         });
 
-        let logging = Arc::new(Logging::new());
-
-        let dongles: Vec<Arc<Dongle>> = (0..args.number_of_coders)
-            .map(|_| Arc::new(Dongle::new(args, Arc::clone(&stop_signal))))
+        let graph = args.graph();
+        // chandy-misra coders pass the dongles around themselves, the arbiter keeps them
+        let number_of_shared_dongles = match args.backend {
+            Backend::Locks => graph.number_of_dongles,
+            Backend::ChandyMisra | Backend::Arbiter => 0,
+        };
+        let dongles: Vec<Arc<Dongle>> = (0..number_of_shared_dongles)
+            .map(|i| {
+                let scheduling = match &args.replay {
+                    Some(recording) => Box::new(Replayed::new(
+                        i + 1,
+                        new_scheduler(i + 1),
+                        recording.order_of(i + 1),
+                    )),
+                    None => new_scheduler(i + 1),
+                };
+                Arc::new(Dongle::new(
+                    i + 1,
+                    args.cooldown_of(i + 1),
+                    args.seed,
+                    scheduling,
+                    Arc::clone(&start_signal),
+                    Arc::clone(&stop_signal),
+                    Arc::clone(&logging),
+                ))
+            })
             .collect();
 
-        let mut coders = Vec::new();
-        // create coders
-        for i in 0..args.number_of_coders {
-            let mut first_idx = i as usize;
-            let mut second_idx = ((i + 1) % args.number_of_coders) as usize;
-
-            if first_idx > second_idx {
-                (first_idx, second_idx) = (second_idx, first_idx);
+        let (waiter, seats) = match args.backend {
+            Backend::Locks => (None, Vec::new()),
+            Backend::ChandyMisra => (
+                None,
+                chandy_misra::seat(&graph, &args)
+                    .into_iter()
+                    .map(DongleAccess::Messages)
+                    .collect(),
+            ),
+            Backend::Arbiter => {
+                let (waiter, seats) = arbiter::seat(&graph, &args, new_scheduler(0));
+                (
+                    Some(waiter),
+                    seats.into_iter().map(DongleAccess::Arbiter).collect(),
+                )
             }
+        };
+        let mut seats = seats.into_iter();
 
-            let first_dongle = Arc::clone(&dongles[first_idx]);
-            let second_dongle = Arc::clone(&dongles[second_idx]);
+        let mut coders = Vec::new();
+        // create coders
+        for (i, dongle_indices) in (1..).zip(&graph.coders) {
+            let coder_dongles = seats.next().unwrap_or_else(|| {
+                // sorted indices, so coders acquire in one global order and can't deadlock
+                DongleAccess::Locks(
+                    dongle_indices
+                        .iter()
+                        .map(|&idx| Arc::clone(&dongles[idx]))
+                        .collect(),
+                )
+            });
 
             let coder = Coder::new(
-                i + 1,
-                args,
-                first_dongle,
-                second_dongle,
+                i,
+                args.coder_profile(i),
+                args.seed,
+                coder_dongles,
                 Arc::clone(&start_signal),
                 Arc::clone(&stop_signal),
                 Arc::clone(&logging),
@@ -67,52 +218,322 @@ impl Codexion {
 
         Self {
             args,
+            graph,
             dongles,
+            waiter,
             coders,
             start_signal,
             stop_signal,
+            paused_at: Mutex::new(None),
+            stop_command: AtomicBool::new(false),
+            clock,
             logging,
+            trace,
+            recorder,
+        }
+    }
+
+    /// Get every event of the run, call it before [`Codexion::start`]
+    pub fn observe(&self, observer: Arc<dyn Observer>) {
+        self.logging.add_observer(observer);
+    }
+
+    /// Run the simulation until every coder is done, one burns out or a
+    /// signal stops it, only once. Signals are only caught once the program
+    /// calls [`signals::install`], and one caught before the start stops the
+    /// run right away: runs going on together all see it
+    pub fn start(&self) -> SimulationOutcome {
+        let outcome = match self.args.clock {
+            ClockMode::Real => self.run_threads(),
+            ClockMode::Virtual => Simulation::new(self).run(),
+        };
+
+        let interrupted = matches!(outcome, SimulationOutcome::Interrupted { .. });
+        if interrupted && self.args.interruption_summary {
+            // whatever an interrupted run got to, it's all there is
+            eprintln!("{}", self.statistics());
+        } else if self.args.stats {
+            self.report_statistics();
+        }
+        if interrupted {
+            let _ = stdout().flush();
+        }
+        self.write_trace();
+        self.write_recording();
+        outcome
+    }
+
+    /// Who holds each dongle and who waits for it, right now
+    pub fn dongle_snapshots(&self) -> Vec<DongleSnapshot> {
+        let now = self.now();
+
+        match &self.waiter {
+            Some(waiter) => waiter.snapshot(now),
+            None => self
+                .dongles
+                .iter()
+                .map(|dongle| dongle.snapshot(now))
+                .collect(),
+        }
+    }
+
+    pub fn wait_for_graph(&self) -> WaitForGraph {
+        WaitForGraph {
+            dongles: self.dongle_snapshots(),
+        }
+    }
+
+    /// What every coder is doing, right now
+    pub fn coder_snapshots(&self) -> Vec<CoderSnapshot> {
+        let now = self.now();
+
+        self.coders
+            .iter()
+            .map(|coder| CoderSnapshot {
+                id: coder.id,
+                phase: coder.phase(),
+                compiles: *coder.compile_count.lock().unwrap(),
+                compiles_required: coder.profile.number_of_compiles_required,
+                since_last_compile: now
+                    .saturating_duration_since(*coder.last_compile_time.lock().unwrap()),
+            })
+            .collect()
+    }
+
+    /// What every coder and dongle is doing, when `coder_id` burns out
+    pub fn post_mortem(&self, coder_id: u32) -> PostMortem {
+        PostMortem {
+            burned_out: Some(coder_id),
+            ..self.snapshot()
+        }
+    }
+
+    /// What every coder and dongle is doing, right now
+    pub fn snapshot(&self) -> PostMortem {
+        PostMortem {
+            burned_out: None,
+            elapsed: self.elapsed(),
+            coders: self.coder_snapshots(),
+            dongles: self.dongle_snapshots(),
+        }
+    }
+
+    /// Write the post-mortem where `--post-mortem` says, a failure doesn't stop the run
+    fn report_post_mortem(&self, post_mortem: &PostMortem) {
+        match self.args.post_mortem.as_deref() {
+            None => (),
+            Some("-") => println!("{post_mortem}"),
+            Some(path) => {
+                if let Err(error) = fs::write(path, format!("{post_mortem}\n")) {
+                    eprintln!("Error: couldn't write the post-mortem to '{path}': {error}");
+                }
+            }
+        }
+    }
+
+    /// Print what the run got to when a signal stopped it, on stderr to keep
+    /// it out of the log
+    fn report_interruption(&self, signal: StopSignal, snapshot: &PostMortem) {
+        if !self.args.interruption_summary {
+            return;
+        }
+        eprintln!(
+            "interrupted by {signal} at {:.1} ms\n",
+            millis(snapshot.elapsed)
+        );
+        eprintln!("{snapshot}");
+    }
+
+    /// Print the state of a run going on, on stderr to keep it out of the log
+    fn report_snapshot(&self) {
+        eprint!("{}", self.snapshot());
+    }
+
+    /// Print the statistics, on stderr when the log is JSON Lines so it stays valid
+    fn report_statistics(&self) {
+        match self.args.log_format {
+            LogFormat::Json => eprintln!("{}", self.statistics()),
+            LogFormat::Text | LogFormat::Quiet | LogFormat::Tui => {
+                println!("{}", self.statistics())
+            }
+        }
+    }
+
+    /// Write the trace where `--trace-out` says, a failure doesn't change the outcome
+    fn write_trace(&self) {
+        let (Some(trace), Some(path)) = (&self.trace, &self.args.trace_out) else {
+            return;
+        };
+        if let Err(error) = fs::write(path, trace.to_json(self.elapsed())) {
+            eprintln!("Error: couldn't write the trace to '{path}': {error}");
+        }
+    }
+
+    /// Write the grants where `--record` says, a failure doesn't change the outcome
+    fn write_recording(&self) {
+        let (Some(recorder), Some(path)) = (&self.recorder, &self.args.record) else {
+            return;
+        };
+        if let Err(error) = fs::write(path, recorder.to_json_lines()) {
+            eprintln!("Error: couldn't write the recording to '{path}': {error}");
         }
     }
 
-    pub fn start(self) {
+    /// How often the watchdog looks, often enough to see a cycle before a burnout
+    fn watchdog_interval(&self) -> Duration {
+        self.args.time_to_burnout / 4
+    }
+
+    /// Time since the logging start, which is the start of the run
+    fn elapsed(&self) -> Duration {
+        let start = *self.logging.start_time_lock.lock().unwrap();
+        self.now().saturating_duration_since(start)
+    }
+
+    /// The clock's time, which stands still while the run is paused
+    fn now(&self) -> Instant {
+        self.paused_at
+            .lock()
+            .unwrap()
+            .unwrap_or_else(|| self.clock.now())
+    }
+
+    /// Summary of the run so far, from what coders and dongles recorded
+    pub fn statistics(&self) -> Statistics {
+        let start = *self.logging.start_time_lock.lock().unwrap();
+        let end = self.now();
+
+        Statistics::new(
+            end.saturating_duration_since(start),
+            self.args.time_to_burnout,
+            self.coders
+                .iter()
+                .map(|coder| coder.statistics(end))
+                .collect(),
+            match self.args.backend {
+                Backend::Locks => self
+                    .dongles
+                    .iter()
+                    .map(|dongle| dongle.statistics(start, end))
+                    .collect(),
+                Backend::Arbiter => self
+                    .waiter
+                    .as_ref()
+                    .map(|waiter| waiter.statistics(start, end))
+                    .unwrap_or_default(),
+                Backend::ChandyMisra => chandy_misra::dongle_statistics(
+                    self.coders.iter().filter_map(|coder| coder.diner()),
+                    self.graph.number_of_dongles,
+                    start,
+                    end,
+                ),
+            },
+        )
+    }
+
+    fn run_threads(&self) -> SimulationOutcome {
         // create all the threads
         let mut handles = Vec::new();
 
         for coder in &self.coders {
-            let coder = Arc::clone(&coder);
+            let coder = Arc::clone(coder);
 
             let handle = thread::spawn(move || coder.start_routine());
             handles.push(handle);
         }
+        if let Some(waiter) = &self.waiter {
+            let waiter = Arc::clone(waiter);
+            handles.push(thread::spawn(move || waiter.run()));
+        }
         // set start time to this instant for logging
         {
             let mut logging_start_time = self.logging.start_time_lock.lock().unwrap();
             *logging_start_time = Instant::now();
         }
         // signal the coders to start
-        {
-            let mut start_mutex = self.start_signal.state.lock().unwrap();
-            *start_mutex = true;
-            self.start_signal.cond.notify_all();
+        self.start_signal.set(true);
+        let dashboard = self.args.log_format == LogFormat::Tui;
+        if dashboard {
+            print!("{}", dashboard::ENTER);
         }
         // start monitoring coders
-        self.monitor();
+        let (outcome, post_mortem) = thread::scope(|scope| {
+            if self.args.watchdog {
+                scope.spawn(|| self.watch());
+            }
+            if dashboard {
+                scope.spawn(|| self.show_dashboard());
+            }
+            if self.args.interactive {
+                scope.spawn(|| self.control());
+            }
+            self.monitor()
+        });
         // join all threads
         for handle in handles {
             handle.join().unwrap();
         }
+        if dashboard {
+            // a burnout is drawn as it was, before the coders left their dongles
+            let frame = match &post_mortem {
+                Some(post_mortem) => Frame {
+                    elapsed: post_mortem.elapsed,
+                    time_to_burnout: self.args.time_to_burnout,
+                    coders: post_mortem.coders.clone(),
+                    dongles: post_mortem.dongles.clone(),
+                    outcome: Some(outcome),
+                },
+                None => self.dashboard_frame(Some(outcome)),
+            };
+            draw_dashboard(&frame);
+            print!("{}", dashboard::LEAVE);
+        }
+        match (outcome, post_mortem) {
+            (_, None) => (),
+            (SimulationOutcome::Interrupted { signal, .. }, Some(snapshot)) => {
+                self.report_interruption(signal, &snapshot)
+            }
+            (_, Some(post_mortem)) => self.report_post_mortem(&post_mortem),
+        }
+        outcome
     }
 
-    fn monitor(&self) {
+    /// Wait for a burnout, every coder to be done or a stop signal, with the
+    /// post-mortem of a burnout or the snapshot of an interruption
+    fn monitor(&self) -> (SimulationOutcome, Option<PostMortem>) {
         loop {
+            let stop = signals::stop_requested().or_else(|| {
+                self.stop_command
+                    .load(Ordering::Relaxed)
+                    .then_some(StopSignal::Command)
+            });
+            if let Some(signal) = stop {
+                // before the coders leave their waiting lines
+                let snapshot = self.snapshot();
+                self.logging.close();
+                self.shutdown();
+                let outcome = SimulationOutcome::Interrupted {
+                    signal,
+                    elapsed: snapshot.elapsed,
+                };
+                return (outcome, Some(snapshot));
+            }
+            if signals::snapshot_requested() {
+                self.report_snapshot();
+            }
+            // nobody burns out while the run is paused
+            if !self.start_signal.is_set() {
+                sleep(SIGNAL_CHECK_INTERVAL);
+                continue;
+            }
+
             let mut all_finished = true;
             let mut earliest_compile_time = Instant::now();
 
             for coder in &self.coders {
                 let compile_count = *coder.compile_count.lock().unwrap();
-                // if coder has reached mandatory compiles, skip him
-                if compile_count == self.args.number_of_compiles_required {
+                // if coder has reached mandatory compiles or was killed, skip him
+                if compile_count == coder.profile.number_of_compiles_required || coder.is_killed() {
                     continue;
                 } else {
                     all_finished = false;
@@ -126,18 +547,79 @@ impl Codexion {
                 // if last compile time is more than burnout time
                 // stop the simulation
                 if Instant::now() - last_compile_time >= self.args.time_to_burnout {
-                    self.shutdown();
                     self.logging.burnout(coder.id);
-                    return;
+                    // before the coders leave their waiting lines
+                    let post_mortem = self.post_mortem(coder.id);
+                    self.shutdown();
+                    let outcome = SimulationOutcome::BurnedOut {
+                        coder_id: coder.id,
+                        elapsed: self.elapsed(),
+                    };
+                    return (outcome, Some(post_mortem));
                 }
             }
 
             if all_finished {
-                break;
+                // let chandy-misra coders who serve their neighbours go
+                self.shutdown();
+                break (SimulationOutcome::Completed, None);
             }
 
             let elapsed = Instant::now() - earliest_compile_time;
-            sleep(self.args.time_to_burnout.saturating_sub(elapsed));
+            sleep(
+                self.args
+                    .time_to_burnout
+                    .saturating_sub(elapsed)
+                    .min(SIGNAL_CHECK_INTERVAL),
+            );
+        }
+    }
+
+    /// Check the wait-for graph every [`Codexion::watchdog_interval`] until the run stops
+    fn watch(&self) {
+        let mut watchdog = Watchdog::default();
+        let mut stop = self.stop_signal.state.lock().unwrap();
+
+        while !*stop {
+            (stop, _) = self
+                .stop_signal
+                .cond
+                .wait_timeout(stop, self.watchdog_interval())
+                .unwrap();
+            if *stop {
+                break;
+            }
+            // snapshots lock the dongles, the coders must be able to stop meanwhile
+            drop(stop);
+            watchdog.check(&self.wait_for_graph(), self.elapsed());
+            stop = self.stop_signal.state.lock().unwrap();
+        }
+    }
+
+    /// Redraw the dashboard every [`dashboard::REFRESH_INTERVAL`] until the run stops
+    fn show_dashboard(&self) {
+        let mut stop = self.stop_signal.state.lock().unwrap();
+
+        while !*stop {
+            // snapshots lock the coders and dongles, they must be able to stop meanwhile
+            drop(stop);
+            draw_dashboard(&self.dashboard_frame(None));
+            stop = self.stop_signal.state.lock().unwrap();
+            (stop, _) = self
+                .stop_signal
+                .cond
+                .wait_timeout(stop, dashboard::REFRESH_INTERVAL)
+                .unwrap();
+        }
+    }
+
+    fn dashboard_frame(&self, outcome: Option<SimulationOutcome>) -> Frame {
+        Frame {
+            elapsed: self.elapsed(),
+            time_to_burnout: self.args.time_to_burnout,
+            coders: self.coder_snapshots(),
+            dongles: self.dongle_snapshots(),
+            outcome,
         }
     }
 
@@ -147,9 +629,20 @@ impl Codexion {
             *stop = true;
         }
         self.stop_signal.cond.notify_all();
+        // paused coders must go on to see it
+        self.start_signal.set(true);
 
         for dongle in &self.dongles {
-            dongle.release_cond.notify_all();
+            dongle.wake_all();
+        }
+        for coder in &self.coders {
+            coder.interrupt();
         }
     }
 }
+
+fn draw_dashboard(frame: &Frame) {
+    let mut stdout = stdout().lock();
+    let _ = write!(stdout, "{frame}");
+    let _ = stdout.flush();
+}
@@ -0,0 +1,180 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::{
+    clock::millis,
+    codexion::{coder::CoderPhase, dongle::DongleSnapshot},
+};
+
+/// State of the whole codexion when a coder burned out, or at any other time
+#[derive(Clone, Debug)]
+pub struct PostMortem {
+    /// `None` for a snapshot of a run going on or interrupted
+    pub burned_out: Option<u32>,
+    /// time since the start of the run
+    pub elapsed: Duration,
+    pub coders: Vec<CoderSnapshot>,
+    /// empty when the coders pass the dongles around themselves
+    pub dongles: Vec<DongleSnapshot>,
+}
+
+/// What a coder was doing at the time of the post-mortem
+#[derive(Clone, Copy, Debug)]
+pub struct CoderSnapshot {
+    pub id: u32,
+    pub phase: CoderPhase,
+    pub compiles: u32,
+    pub compiles_required: u32,
+    pub since_last_compile: Duration,
+}
+
+impl PostMortem {
+    /// From the burned out coder, who each one waits for: the dongle he
+    /// waits on, and its holder or whoever is before him in line
+    pub fn chain(&self) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut visited = Vec::new();
+        let Some(mut coder_id) = self.burned_out else {
+            return chain;
+        };
+
+        loop {
+            if visited.contains(&coder_id) {
+                chain.push(format!("coder {coder_id} again, they wait on each other"));
+                break;
+            }
+            visited.push(coder_id);
+            let Some(coder) = self.coders.iter().find(|coder| coder.id == coder_id) else {
+                break;
+            };
+            let CoderPhase::Acquiring {
+                dongle_id: Some(dongle_id),
+            } = coder.phase
+            else {
+                chain.push(format!("coder {coder_id} is {}", coder.phase));
+                break;
+            };
+            let Some(dongle) = self.dongles.iter().find(|dongle| dongle.id == dongle_id) else {
+                break;
+            };
+
+            let ahead = dongle.waiting.first().copied().filter(|&id| id != coder_id);
+            match (dongle.holder, ahead, dongle.cooldown_left) {
+                (Some(holder), _, _) => {
+                    chain.push(format!(
+                        "coder {coder_id} waits for dongle {dongle_id}, held by coder {holder}"
+                    ));
+                    coder_id = holder;
+                }
+                (None, Some(ahead), _) => {
+                    chain.push(format!(
+                        "coder {coder_id} waits for dongle {dongle_id}, coder {ahead} is first in line"
+                    ));
+                    coder_id = ahead;
+                }
+                (None, None, Some(left)) => {
+                    chain.push(format!(
+                        "coder {coder_id} waits for dongle {dongle_id} to cool down, {:.1} ms left",
+                        millis(left)
+                    ));
+                    break;
+                }
+                (None, None, None) => {
+                    chain.push(format!(
+                        "coder {coder_id} was about to take dongle {dongle_id}"
+                    ));
+                    break;
+                }
+            }
+        }
+        chain
+    }
+}
+
+fn coder_list(coder_ids: &[u32]) -> String {
+    if coder_ids.is_empty() {
+        return "-".to_string();
+    }
+    coder_ids
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Display for CoderPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoderPhase::Starting => write!(f, "starting"),
+            CoderPhase::Acquiring {
+                dongle_id: Some(dongle_id),
+            } => write!(f, "waiting for dongle {dongle_id}"),
+            CoderPhase::Acquiring { dongle_id: None } => write!(f, "waiting for his dongles"),
+            CoderPhase::Compiling => write!(f, "compiling"),
+            CoderPhase::Debugging => write!(f, "debugging"),
+            CoderPhase::Refactoring => write!(f, "refactoring"),
+            CoderPhase::Done => write!(f, "done"),
+            CoderPhase::Killed => write!(f, "killed"),
+        }
+    }
+}
+
+impl Display for PostMortem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.burned_out {
+            Some(coder_id) => writeln!(
+                f,
+                "=== Post-mortem: coder {coder_id} burned out at {:.1} ms ===",
+                millis(self.elapsed)
+            )?,
+            None => writeln!(f, "=== Snapshot at {:.1} ms ===", millis(self.elapsed))?,
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>8}  {:<24} {:>9} {:>24}",
+            "coder", "phase", "compiles", "since last compile ms"
+        )?;
+        for coder in &self.coders {
+            writeln!(
+                f,
+                "{:>8}  {:<24} {:>9} {:>24.1}",
+                coder.id,
+                coder.phase.to_string(),
+                format!("{}/{}", coder.compiles, coder.compiles_required),
+                millis(coder.since_last_compile)
+            )?;
+        }
+
+        writeln!(f)?;
+        if self.dongles.is_empty() {
+            writeln!(
+                f,
+                "dongles are passed between coders, there's no shared state to show"
+            )?;
+        } else {
+            writeln!(f, "{:>8}  {:<28} waiting line", "dongle", "state")?;
+            for dongle in &self.dongles {
+                let state = match (dongle.holder, dongle.cooldown_left) {
+                    (Some(holder), _) => format!("held by coder {holder}"),
+                    (None, Some(left)) => format!("cooling down, {:.1} ms left", millis(left)),
+                    (None, None) => "available".to_string(),
+                };
+                writeln!(
+                    f,
+                    "{:>8}  {:<28} {}",
+                    dongle.id,
+                    state,
+                    coder_list(&dongle.waiting)
+                )?;
+            }
+        }
+
+        let chain = self.chain();
+        if !chain.is_empty() {
+            writeln!(f)?;
+            write!(f, "{}", chain.join("\n"))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    args::{Args, SchedulerKind},
+    rng::Rng,
+};
+
+/// Waiting line of a dongle, it decides which coder gets the dongle next.
+///
+/// Every dongle owns its own scheduler, or the arbiter owns a single one for
+/// all of them, so implementations don't need any locking, their owner already
+/// holds its lock when calling them.
+pub trait Scheduler: Send {
+    /// Put a coder at its place in the line
+    fn enqueue(&mut self, request: Request);
+
+    /// Id of the coder that gets the dongle next
+    fn peek(&self) -> Option<u32>;
+
+    /// Take the coder that's next out of the line
+    fn pop(&mut self) -> Option<u32>;
+
+    /// The dongle is free for whoever's next, called before peeking at him.
+    /// A line that draws its next coder does it here, among everyone in it.
+    fn choose(&mut self) {}
+
+    /// Take a coder out of the line wherever he is, when he stops waiting
+    fn remove(&mut self, coder_id: u32);
+
+    /// Ids of the coders in line, the next one first
+    fn waiting(&self) -> Vec<u32>;
+}
+
+/// A coder asking for a dongle
+#[derive(Clone, Copy, Debug)]
+pub struct Request {
+    pub coder_id: u32,
+    pub last_compile_time: Instant,
+}
+
+/// First In First Out
+#[derive(Default)]
+pub struct Fifo {
+    queue: VecDeque<u32>,
+}
+
+/// Earliest Deadline First, the coder who compiled the longest ago goes first
+#[derive(Default)]
+pub struct Edf {
+    line: ByDeadline,
+}
+
+/// Least Laxity First, the coder with the least slack before he burns out,
+/// counting the time he needs to compile, goes first
+pub struct Llf {
+    time_to_burnout: Duration,
+    /// per coder, the time he needs to compile on average
+    compile_times: Vec<Duration>,
+    line: ByDeadline,
+}
+
+/// Coders by a deadline worked out when they join, the earliest first
+#[derive(Default)]
+struct ByDeadline {
+    heap: BinaryHeap<(Reverse<Instant>, u32)>,
+}
+
+/// Lottery, every coder in line when the dongle is free has the same chance
+/// to go next
+pub struct Lottery {
+    rng: Rng,
+    line: Vec<u32>,
+    /// drawn when the dongle is free, so peeking always gives the same coder
+    /// until he gets it
+    winner: Option<u32>,
+}
+
+/// Round-robin, the next one is the first waiting after the last served, by coder id
+#[derive(Default)]
+pub struct RoundRobin {
+    line: BTreeSet<u32>,
+    last_served: Option<u32>,
+}
+
+/// Priority with aging, the lower the coder id the higher his priority, and
+/// every turn he loses in line raises it by one
+#[derive(Default)]
+pub struct Aging {
+    /// coder id and turns lost, in arrival order
+    line: Vec<(u32, u32)>,
+}
+
+impl SchedulerKind {
+    /// A new waiting line of this kind for dongle `dongle_id`, or for the
+    /// arbiter when it's 0, in a codexion with these arguments
+    pub fn build(self, args: &Args, dongle_id: u32) -> Box<dyn Scheduler> {
+        match self {
+            SchedulerKind::Fifo => Box::new(Fifo::default()),
+            SchedulerKind::Edf => Box::new(Edf::default()),
+            SchedulerKind::Llf => Box::new(Llf::new(args)),
+            SchedulerKind::Lottery { seed } => {
+                Box::new(Lottery::new(seed.unwrap_or(args.seed), dongle_id))
+            }
+            SchedulerKind::RoundRobin => Box::new(RoundRobin::default()),
+            SchedulerKind::Aging => Box::new(Aging::default()),
+        }
+    }
+}
+
+impl Scheduler for Fifo {
+    fn enqueue(&mut self, request: Request) {
+        self.queue.push_back(request.coder_id);
+    }
+
+    fn peek(&self) -> Option<u32> {
+        self.queue.front().copied()
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        self.queue.pop_front()
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.queue.retain(|&id| id != coder_id);
+    }
+
+    fn waiting(&self) -> Vec<u32> {
+        self.queue.iter().copied().collect()
+    }
+}
+
+impl ByDeadline {
+    fn push(&mut self, deadline: Instant, coder_id: u32) {
+        self.heap.push((Reverse(deadline), coder_id));
+    }
+
+    fn peek(&self) -> Option<u32> {
+        self.heap.peek().map(|&(_, coder_id)| coder_id)
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        self.heap.pop().map(|(_, coder_id)| coder_id)
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.heap.retain(|&(_, id)| id != coder_id);
+    }
+
+    fn waiting(&self) -> Vec<u32> {
+        let mut line = self.heap.clone().into_sorted_vec();
+        line.reverse();
+        line.into_iter().map(|(_, coder_id)| coder_id).collect()
+    }
+}
+
+impl Scheduler for Edf {
+    fn enqueue(&mut self, request: Request) {
+        self.line.push(request.last_compile_time, request.coder_id);
+    }
+
+    fn peek(&self) -> Option<u32> {
+        self.line.peek()
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        self.line.pop()
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.line.remove(coder_id);
+    }
+
+    fn waiting(&self) -> Vec<u32> {
+        self.line.waiting()
+    }
+}
+
+impl Llf {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            time_to_burnout: args.time_to_burnout,
+            compile_times: (1..=args.number_of_coders)
+                .map(|coder_id| args.coder_profile(coder_id).time_to_compile.mean())
+                .collect(),
+            line: ByDeadline::default(),
+        }
+    }
+
+    /// Latest time he can start compiling without burning out
+    fn laxity_deadline(&self, request: &Request) -> Instant {
+        let time_to_compile = (request.coder_id as usize)
+            .checked_sub(1)
+            .and_then(|idx| self.compile_times.get(idx))
+            .unwrap_or_else(|| panic!("coder {} isn't in the codexion", request.coder_id));
+        // a coder who can't compile in time has no slack at all
+        request.last_compile_time + self.time_to_burnout.saturating_sub(*time_to_compile)
+    }
+}
+
+impl Scheduler for Llf {
+    fn enqueue(&mut self, request: Request) {
+        let laxity_deadline = self.laxity_deadline(&request);
+        self.line.push(laxity_deadline, request.coder_id);
+    }
+
+    fn peek(&self) -> Option<u32> {
+        self.line.peek()
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        self.line.pop()
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.line.remove(coder_id);
+    }
+
+    fn waiting(&self) -> Vec<u32> {
+        self.line.waiting()
+    }
+}
+
+impl Lottery {
+    /// The lottery of dongle `dongle_id`, each dongle draws from a stream of
+    /// its own so they don't all pick the same places in their lines
+    pub fn new(seed: u64, dongle_id: u32) -> Self {
+        Self {
+            rng: Rng::for_lottery(seed, dongle_id),
+            line: Vec::new(),
+            winner: None,
+        }
+    }
+}
+
+impl Scheduler for Lottery {
+    fn enqueue(&mut self, request: Request) {
+        self.line.push(request.coder_id);
+    }
+
+    fn peek(&self) -> Option<u32> {
+        self.winner
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        let winner = self.winner?;
+        self.remove(winner);
+        Some(winner)
+    }
+
+    fn choose(&mut self) {
+        if self.winner.is_none() && !self.line.is_empty() {
+            let len = self.line.len() as u64;
+            self.winner = Some(self.line[self.rng.below(len) as usize]);
+        }
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.line.retain(|&id| id != coder_id);
+        if self.winner == Some(coder_id) {
+            self.winner = None;
+        }
+    }
+
+    /// The winner, then the others in arrival order
+    fn waiting(&self) -> Vec<u32> {
+        self.winner
+            .into_iter()
+            .chain(
+                self.line
+                    .iter()
+                    .copied()
+                    .filter(|&id| Some(id) != self.winner),
+            )
+            .collect()
+    }
+}
+
+impl Scheduler for RoundRobin {
+    fn enqueue(&mut self, request: Request) {
+        self.line.insert(request.coder_id);
+    }
+
+    fn peek(&self) -> Option<u32> {
+        let after = self.last_served.map_or(0, |id| id + 1);
+        self.line
+            .range(after..)
+            .chain(self.line.range(..after))
+            .next()
+            .copied()
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        let coder_id = self.peek()?;
+        self.line.remove(&coder_id);
+        self.last_served = Some(coder_id);
+        Some(coder_id)
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.line.remove(&coder_id);
+    }
+
+    fn waiting(&self) -> Vec<u32> {
+        let after = self.last_served.map_or(0, |id| id + 1);
+        self.line
+            .range(after..)
+            .chain(self.line.range(..after))
+            .copied()
+            .collect()
+    }
+}
+
+impl Aging {
+    /// Effective priority, then the lower id, as a key where the smallest goes first
+    fn key(&(coder_id, turns_lost): &(u32, u32)) -> (i64, u32) {
+        (coder_id as i64 - turns_lost as i64, coder_id)
+    }
+}
+
+impl Scheduler for Aging {
+    fn enqueue(&mut self, request: Request) {
+        self.line.push((request.coder_id, 0));
+    }
+
+    fn peek(&self) -> Option<u32> {
+        self.line
+            .iter()
+            .min_by_key(|entry| Self::key(entry))
+            .map(|&(coder_id, _)| coder_id)
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        let coder_id = self.peek()?;
+        self.remove(coder_id);
+        for (_, turns_lost) in &mut self.line {
+            *turns_lost += 1;
+        }
+        Some(coder_id)
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.line.retain(|&(id, _)| id != coder_id);
+    }
+
+    fn waiting(&self) -> Vec<u32> {
+        let mut line = self.line.clone();
+        line.sort_by_key(Self::key);
+        line.into_iter().map(|(coder_id, _)| coder_id).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::CoderOverride;
+
+    fn enqueue(scheduler: &mut dyn Scheduler, coder_ids: impl IntoIterator<Item = u32>) {
+        let now = Instant::now();
+        for coder_id in coder_ids {
+            scheduler.enqueue(Request {
+                coder_id,
+                last_compile_time: now,
+            });
+        }
+    }
+
+    /// Winners of `rounds` draws between coders 1 to 4, who all join the
+    /// line before the dongle is free
+    fn winners(mut lottery: Lottery, rounds: usize) -> Vec<u32> {
+        (0..rounds)
+            .map(|_| {
+                enqueue(&mut lottery, 1..=4);
+                lottery.choose();
+                let winner = lottery.pop().unwrap();
+                for coder_id in 1..=4 {
+                    lottery.remove(coder_id);
+                }
+                winner
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lottery_draws_are_reproducible_for_each_dongle() {
+        let first = winners(Lottery::new(42, 1), 100);
+        assert_eq!(first, winners(Lottery::new(42, 1), 100));
+        assert_ne!(first, winners(Lottery::new(42, 2), 100));
+        assert_ne!(first, winners(Lottery::new(43, 1), 100));
+    }
+
+    #[test]
+    fn lottery_draws_among_everyone_in_line() {
+        let rounds = 8000;
+        let winners = winners(Lottery::new(7, 1), rounds);
+
+        // the first one in line has no edge over the others
+        for coder_id in 1..=4 {
+            let share = winners.iter().filter(|&&id| id == coder_id).count() as f64 / rounds as f64;
+            assert!((share - 0.25).abs() < 0.03, "coder {coder_id} won {share}");
+        }
+    }
+
+    #[test]
+    fn lottery_draws_when_the_dongle_is_free() {
+        let mut lottery = Lottery::new(3, 1);
+        enqueue(&mut lottery, [2]);
+        assert_eq!(lottery.peek(), None);
+
+        enqueue(&mut lottery, [5, 3]);
+        while !lottery.waiting().is_empty() {
+            lottery.choose();
+            let next = lottery.peek().unwrap();
+            assert_eq!(lottery.waiting()[0], next);
+            // drawn once until he gets it
+            lottery.choose();
+            assert_eq!(lottery.pop(), Some(next));
+        }
+        assert_eq!(lottery.peek(), None);
+    }
+
+    #[test]
+    fn llf_counts_the_compile_time_edf_does_not() {
+        let args = Args::builder()
+            .number_of_coders(2)
+            .time_to_burnout(Duration::from_millis(1000))
+            .coder_override(
+                2,
+                CoderOverride {
+                    time_to_compile: Some(Duration::from_millis(500).into()),
+                    ..Default::default()
+                },
+            )
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        // coder 1 compiled 100ms before coder 2, but needs 300ms less to compile
+        let requests = [
+            Request {
+                coder_id: 1,
+                last_compile_time: now,
+            },
+            Request {
+                coder_id: 2,
+                last_compile_time: now + Duration::from_millis(100),
+            },
+        ];
+
+        let mut edf = Edf::default();
+        let mut llf = Llf::new(&args);
+        for request in requests {
+            edf.enqueue(request);
+            llf.enqueue(request);
+        }
+        assert_eq!(edf.waiting(), vec![1, 2]);
+        assert_eq!(llf.waiting(), vec![2, 1]);
+
+        llf.remove(2);
+        assert_eq!(llf.pop(), Some(1));
+        assert_eq!(llf.peek(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "coder 3 isn't in the codexion")]
+    fn llf_rejects_an_unknown_coder() {
+        let args = Args::builder().number_of_coders(2).build().unwrap();
+        Llf::new(&args).enqueue(Request {
+            coder_id: 3,
+            last_compile_time: Instant::now(),
+        });
+    }
+}
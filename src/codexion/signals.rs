@@ -0,0 +1,100 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+};
+
+/// A signal that stops the run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopSignal {
+    /// Ctrl-C
+    Interrupt,
+    Terminate,
+    /// the `stop` command of the interactive mode, which stops like Ctrl-C
+    Command,
+}
+
+/// Number of the stop signal caught, 0 until there's one
+static PENDING_STOP: AtomicI32 = AtomicI32::new(0);
+/// SIGUSR1 was caught and nobody printed the snapshot yet
+static PENDING_SNAPSHOT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod ffi {
+    use std::ffi::c_int;
+
+    pub const SIGINT: c_int = 2;
+    pub const SIGTERM: c_int = 15;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub const SIGUSR1: c_int = 10;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub const SIGUSR1: c_int = 30;
+
+    unsafe extern "C" {
+        /// the handler stays installed and interrupted calls restart
+        pub fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        pub fn _exit(status: c_int) -> !;
+    }
+}
+
+impl StopSignal {
+    pub fn number(self) -> i32 {
+        match self {
+            StopSignal::Interrupt | StopSignal::Command => 2,
+            StopSignal::Terminate => 15,
+        }
+    }
+}
+
+impl Display for StopSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopSignal::Interrupt => write!(f, "SIGINT"),
+            StopSignal::Terminate => write!(f, "SIGTERM"),
+            StopSignal::Command => write!(f, "the stop command"),
+        }
+    }
+}
+
+/// Only sets a flag, the monitor does the rest outside of the handler
+#[cfg(unix)]
+extern "C" fn on_signal(signum: std::ffi::c_int) {
+    if signum == ffi::SIGUSR1 {
+        PENDING_SNAPSHOT.store(true, Ordering::Relaxed);
+        return;
+    }
+    // a second one means the shutdown is stuck, leave right away
+    if PENDING_STOP.swap(signum, Ordering::Relaxed) != 0 {
+        unsafe { ffi::_exit(128 + signum) }
+    }
+}
+
+/// Catch SIGINT and SIGTERM to stop the run, and SIGUSR1 to print a snapshot.
+/// The handlers are the whole process's, so the library leaves it to the program
+pub fn install() {
+    #[cfg(unix)]
+    for signum in [ffi::SIGINT, ffi::SIGTERM, ffi::SIGUSR1] {
+        unsafe {
+            ffi::signal(signum, on_signal);
+        }
+    }
+}
+
+/// The stop signal caught, if any
+pub fn stop_requested() -> Option<StopSignal> {
+    match PENDING_STOP.load(Ordering::Relaxed) {
+        2 => Some(StopSignal::Interrupt),
+        15 => Some(StopSignal::Terminate),
+        _ => None,
+    }
+}
+
+/// Forget a stop signal caught before, once before a batch of runs, never
+/// while one goes on
+pub fn clear_stop() {
+    PENDING_STOP.store(0, Ordering::Relaxed);
+}
+
+/// Whether a snapshot was asked for since the last call
+pub fn snapshot_requested() -> bool {
+    PENDING_SNAPSHOT.swap(false, Ordering::Relaxed)
+}
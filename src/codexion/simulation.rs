@@ -0,0 +1,346 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    time::{Duration, Instant},
+};
+
+use crate::codexion::{
+    Codexion, SimulationOutcome, coder::CoderPhase, dongle::Acquisition, signals,
+    watchdog::Watchdog,
+};
+
+/// Discrete-event run of a codexion on a virtual clock.
+///
+/// Everything happens on the calling thread, the clock jumps from one event
+/// to the next, so the same arguments always give the same run.
+pub struct Simulation<'a> {
+    codexion: &'a Codexion,
+    events: BinaryHeap<Reverse<ScheduledEvent>>,
+    sequence: u64,
+    coders: Vec<CoderState>,
+    /// coders waiting on each dongle, in the order they started waiting
+    parked: Vec<Vec<usize>>,
+    watchdog: Option<Watchdog>,
+}
+
+struct CoderState {
+    /// indices in the global acquiring order
+    dongles: Vec<usize>,
+    phase: Phase,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Starting,
+    /// waiting for the `nth` of his dongles
+    Acquiring(usize),
+    Compiling,
+    Debugging,
+    Refactoring,
+    Done,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct ScheduledEvent {
+    at: Instant,
+    sequence: u64,
+    event: Event,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    /// the coder's current phase is over
+    Resume(usize),
+    /// the dongle's cooldown is over
+    DongleReady(usize),
+    /// the coder might have burned out
+    BurnoutCheck(usize),
+    /// time for the watchdog to look at the wait-for graph
+    WatchdogCheck,
+}
+
+impl<'a> Simulation<'a> {
+    pub fn new(codexion: &'a Codexion) -> Self {
+        let coders = codexion
+            .graph
+            .coders
+            .iter()
+            .map(|dongles| CoderState {
+                dongles: dongles.clone(),
+                phase: Phase::Starting,
+            })
+            .collect();
+
+        Self {
+            codexion,
+            events: BinaryHeap::new(),
+            sequence: 0,
+            coders,
+            parked: vec![Vec::new(); codexion.dongles.len()],
+            watchdog: codexion.args.watchdog.then(Watchdog::default),
+        }
+    }
+
+    pub fn run(mut self) -> SimulationOutcome {
+        let start = self.codexion.clock.now();
+
+        for (idx, coder) in self.codexion.coders.iter().enumerate() {
+            *coder.last_compile_time.lock().unwrap() = start;
+            self.schedule(start, Event::Resume(idx));
+            self.schedule(
+                start + self.codexion.args.time_to_burnout,
+                Event::BurnoutCheck(idx),
+            );
+        }
+        if self.watchdog.is_some() {
+            let interval = self.codexion.watchdog_interval();
+            self.schedule(start + interval, Event::WatchdogCheck);
+        }
+
+        while let Some(Reverse(scheduled)) = self.events.pop() {
+            if let Some(signal) = signals::stop_requested() {
+                self.codexion.logging.close();
+                let elapsed = self.codexion.elapsed();
+                self.codexion
+                    .report_interruption(signal, &self.codexion.snapshot());
+                return SimulationOutcome::Interrupted { signal, elapsed };
+            }
+            if signals::snapshot_requested() {
+                self.codexion.report_snapshot();
+            }
+            self.codexion.clock.advance_to(scheduled.at);
+            let now = scheduled.at;
+
+            match scheduled.event {
+                Event::Resume(idx) => self.resume(idx, now),
+                Event::DongleReady(dongle_idx) => match self.codexion.waiter {
+                    Some(_) => self.grant(now),
+                    None => self.wake_parked(dongle_idx, now),
+                },
+                Event::BurnoutCheck(idx) => {
+                    if self.has_burned_out(idx, now) {
+                        let coder_id = self.codexion.coders[idx].id;
+                        self.codexion.logging.burnout(coder_id);
+                        let post_mortem = self.codexion.post_mortem(coder_id);
+                        self.codexion.report_post_mortem(&post_mortem);
+                        return SimulationOutcome::BurnedOut {
+                            coder_id,
+                            elapsed: now - start,
+                        };
+                    }
+                }
+                Event::WatchdogCheck => self.watch(now, now - start),
+            }
+        }
+        SimulationOutcome::Completed
+    }
+
+    fn schedule(&mut self, at: Instant, event: Event) {
+        self.sequence += 1;
+        self.events.push(Reverse(ScheduledEvent {
+            at,
+            sequence: self.sequence,
+            event,
+        }));
+    }
+
+    fn schedule_after(&mut self, now: Instant, duration: Duration, event: Event) {
+        self.schedule(now + duration, event);
+    }
+
+    /// Move the coder on to its next phase
+    fn resume(&mut self, idx: usize, now: Instant) {
+        let time_to_burnout = self.codexion.args.time_to_burnout;
+        let coder = &self.codexion.coders[idx];
+        let profile = coder.profile;
+
+        match self.coders[idx].phase {
+            Phase::Starting => self.request_dongles(idx, now),
+            Phase::Acquiring(_) | Phase::Done => (),
+            Phase::Compiling => {
+                match &self.codexion.waiter {
+                    Some(waiter) => {
+                        let cooldowns = waiter.cooldowns(idx);
+                        for (&dongle_idx, &cooldown) in
+                            self.coders[idx].dongles.iter().zip(&cooldowns).rev()
+                        {
+                            self.codexion.logging.release(
+                                coder.id,
+                                dongle_idx as u32 + 1,
+                                cooldown,
+                            );
+                        }
+                        waiter.release(idx, now);
+                        for (nth, cooldown) in cooldowns.into_iter().enumerate() {
+                            let dongle_idx = self.coders[idx].dongles[nth];
+                            self.schedule_after(now, cooldown, Event::DongleReady(dongle_idx));
+                        }
+                    }
+                    None => {
+                        for nth in (0..self.coders[idx].dongles.len()).rev() {
+                            let dongle_idx = self.coders[idx].dongles[nth];
+                            let cooldown =
+                                self.codexion.dongles[dongle_idx].release_at(coder.id, now);
+                            self.schedule_after(now, cooldown, Event::DongleReady(dongle_idx));
+                        }
+                    }
+                }
+
+                coder.record_compile(now);
+                self.schedule_after(now, time_to_burnout, Event::BurnoutCheck(idx));
+
+                let time_to_debug = coder.next_time_to_debug();
+                self.codexion.logging.debug(coder.id, time_to_debug);
+                self.set_phase(idx, Phase::Debugging);
+                self.schedule_after(now, time_to_debug, Event::Resume(idx));
+            }
+            Phase::Debugging => {
+                let time_to_refactor = coder.next_time_to_refactor();
+                self.codexion.logging.refactor(coder.id, time_to_refactor);
+                self.set_phase(idx, Phase::Refactoring);
+                self.schedule_after(now, time_to_refactor, Event::Resume(idx));
+            }
+            Phase::Refactoring => {
+                if *coder.compile_count.lock().unwrap() == profile.number_of_compiles_required {
+                    self.set_phase(idx, Phase::Done);
+                } else {
+                    self.request_dongles(idx, now);
+                }
+            }
+        }
+    }
+
+    /// Move the coder on to `phase`, and let him tell it
+    fn set_phase(&mut self, idx: usize, phase: Phase) {
+        self.coders[idx].phase = phase;
+
+        let coder_phase = match phase {
+            Phase::Starting => CoderPhase::Starting,
+            // the arbiter hands them all at once
+            Phase::Acquiring(nth) => CoderPhase::Acquiring {
+                dongle_id: match self.codexion.waiter {
+                    Some(_) => None,
+                    None => Some(self.coders[idx].dongles[nth] as u32 + 1),
+                },
+            },
+            Phase::Compiling => CoderPhase::Compiling,
+            Phase::Debugging => CoderPhase::Debugging,
+            Phase::Refactoring => CoderPhase::Refactoring,
+            Phase::Done => CoderPhase::Done,
+        };
+        self.codexion.coders[idx].set_phase(coder_phase);
+    }
+
+    /// Ask for the coder's dongles, to the arbiter or starting with the first one
+    fn request_dongles(&mut self, idx: usize, now: Instant) {
+        let Some(waiter) = &self.codexion.waiter else {
+            self.request_dongle(idx, 0, now);
+            return;
+        };
+
+        let last_compile_time = *self.codexion.coders[idx].last_compile_time.lock().unwrap();
+        waiter.ask(idx, last_compile_time, now);
+        self.set_phase(idx, Phase::Acquiring(0));
+        self.grant(now);
+    }
+
+    /// Start the coders the arbiter gives their dongles to
+    fn grant(&mut self, now: Instant) {
+        let Some(waiter) = &self.codexion.waiter else {
+            return;
+        };
+
+        for idx in waiter.grant(now) {
+            let coder_id = self.codexion.coders[idx].id;
+            for &dongle_idx in &self.coders[idx].dongles {
+                self.codexion
+                    .logging
+                    .acquire(coder_id, dongle_idx as u32 + 1);
+            }
+            self.start_compiling(idx, now);
+        }
+    }
+
+    fn start_compiling(&mut self, idx: usize, now: Instant) {
+        let coder = &self.codexion.coders[idx];
+        let time_to_compile = coder.next_time_to_compile();
+        self.codexion.logging.compile(coder.id, time_to_compile);
+        self.set_phase(idx, Phase::Compiling);
+        self.schedule_after(now, time_to_compile, Event::Resume(idx));
+    }
+
+    /// Put the coder in the waiting line of its `nth` dongle and try to take it
+    fn request_dongle(&mut self, idx: usize, nth: usize, now: Instant) {
+        let coder = &self.codexion.coders[idx];
+        let dongle_idx = self.coders[idx].dongles[nth];
+        let last_compile_time = *coder.last_compile_time.lock().unwrap();
+
+        self.codexion.dongles[dongle_idx].join_line(coder.id, last_compile_time, now);
+        self.set_phase(idx, Phase::Acquiring(nth));
+
+        if !self.try_take(idx, dongle_idx, now) {
+            self.parked[dongle_idx].push(idx);
+        }
+    }
+
+    /// Give the dongle to the coder if it's his turn, and carry on from there
+    fn try_take(&mut self, idx: usize, dongle_idx: usize, now: Instant) -> bool {
+        let coder = &self.codexion.coders[idx];
+
+        match self.codexion.dongles[dongle_idx].try_take(coder.id, now) {
+            Acquisition::Granted => (),
+            Acquisition::CoolingDownUntil(_) | Acquisition::Busy => return false,
+        }
+
+        let Phase::Acquiring(nth) = self.coders[idx].phase else {
+            unreachable!("coder {} took a dongle without asking", coder.id);
+        };
+        self.codexion
+            .logging
+            .acquire(coder.id, self.codexion.dongles[dongle_idx].id);
+
+        if nth + 1 < self.coders[idx].dongles.len() {
+            self.request_dongle(idx, nth + 1, now);
+        } else {
+            self.start_compiling(idx, now);
+        }
+        true
+    }
+
+    /// Offer a dongle that cooled down to the coders waiting on it
+    fn wake_parked(&mut self, dongle_idx: usize, now: Instant) {
+        let mut position = 0;
+
+        while position < self.parked[dongle_idx].len() {
+            let idx = self.parked[dongle_idx].remove(position);
+            if self.try_take(idx, dongle_idx, now) {
+                break;
+            }
+            self.parked[dongle_idx].insert(position, idx);
+            position += 1;
+        }
+    }
+
+    /// Check the wait-for graph, and again later while someone still has compiles to do
+    fn watch(&mut self, now: Instant, elapsed: Duration) {
+        let graph = self.codexion.wait_for_graph();
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.check(&graph, elapsed);
+        }
+
+        if self.coders.iter().any(|coder| coder.phase != Phase::Done) {
+            let interval = self.codexion.watchdog_interval();
+            self.schedule_after(now, interval, Event::WatchdogCheck);
+        }
+    }
+
+    fn has_burned_out(&self, idx: usize, now: Instant) -> bool {
+        let coder = &self.codexion.coders[idx];
+
+        if *coder.compile_count.lock().unwrap() == coder.profile.number_of_compiles_required {
+            return false;
+        }
+
+        now - *coder.last_compile_time.lock().unwrap() >= self.codexion.args.time_to_burnout
+    }
+}
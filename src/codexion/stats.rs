@@ -0,0 +1,149 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::clock::millis;
+
+/// Summary of a finished run, built from what coders and dongles recorded
+#[derive(Clone, Debug)]
+pub struct Statistics {
+    pub duration: Duration,
+    pub time_to_burnout: Duration,
+    pub coders: Vec<CoderStatistics>,
+    pub dongles: Vec<DongleStatistics>,
+    /// Jain fairness index of the compile counts, 1 means perfectly fair
+    pub fairness: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct CoderStatistics {
+    pub id: u32,
+    pub compiles: u32,
+    /// longest time spent without compiling, counting the end of the run
+    pub longest_gap: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct DongleStatistics {
+    pub id: u32,
+    pub waits: WaitSummary,
+    /// share of the run the dongle was held
+    pub utilisation: f64,
+    pub cooldown: Duration,
+}
+
+/// Distribution of the time coders waited to get a dongle
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WaitSummary {
+    pub count: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl Statistics {
+    pub fn new(
+        duration: Duration,
+        time_to_burnout: Duration,
+        coders: Vec<CoderStatistics>,
+        dongles: Vec<DongleStatistics>,
+    ) -> Self {
+        let compiles: Vec<f64> = coders.iter().map(|coder| coder.compiles as f64).collect();
+        let fairness = jain_index(&compiles);
+
+        Self {
+            duration,
+            time_to_burnout,
+            coders,
+            dongles,
+            fairness,
+        }
+    }
+
+    pub fn total_compiles(&self) -> u32 {
+        self.coders.iter().map(|coder| coder.compiles).sum()
+    }
+}
+
+impl WaitSummary {
+    pub fn new(mut waits: Vec<Duration>) -> Self {
+        if waits.is_empty() {
+            return Self::default();
+        }
+        waits.sort();
+
+        let count = waits.len();
+        // nearest rank percentile
+        let p95_rank = (count * 95).div_ceil(100).max(1);
+
+        Self {
+            count,
+            min: waits[0],
+            mean: waits.iter().sum::<Duration>() / count as u32,
+            p95: waits[p95_rank - 1],
+            max: waits[count - 1],
+        }
+    }
+}
+
+/// `(sum x)^2 / (n * sum x^2)`, from 1/n when one coder gets everything to 1
+pub fn jain_index(values: &[f64]) -> f64 {
+    let sum: f64 = values.iter().sum();
+    let sum_of_squares: f64 = values.iter().map(|value| value * value).sum();
+
+    if sum_of_squares == 0.0 {
+        return 1.0;
+    }
+    sum * sum / (values.len() as f64 * sum_of_squares)
+}
+
+impl Display for Statistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "=== Statistics ({:.1} ms) ===", millis(self.duration))?;
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>8} {:>9} {:>16} {:>10}",
+            "coder", "compiles", "longest gap ms", "burnout %"
+        )?;
+        for coder in &self.coders {
+            writeln!(
+                f,
+                "{:>8} {:>9} {:>16.1} {:>9.0}%",
+                coder.id,
+                coder.compiles,
+                millis(coder.longest_gap),
+                coder.longest_gap.as_secs_f64() / self.time_to_burnout.as_secs_f64() * 100.0
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>8} {:>6} {:>9} {:>9} {:>9} {:>9} {:>8} {:>12}",
+            "dongle", "waits", "min ms", "mean ms", "p95 ms", "max ms", "used %", "cooldown ms"
+        )?;
+        for dongle in &self.dongles {
+            writeln!(
+                f,
+                "{:>8} {:>6} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>7.1}% {:>12.1}",
+                dongle.id,
+                dongle.waits.count,
+                millis(dongle.waits.min),
+                millis(dongle.waits.mean),
+                millis(dongle.waits.p95),
+                millis(dongle.waits.max),
+                dongle.utilisation * 100.0,
+                millis(dongle.cooldown)
+            )?;
+        }
+
+        writeln!(f)?;
+        write!(
+            f,
+            "total compiles: {}, Jain fairness index: {:.3}",
+            self.total_compiles(),
+            self.fairness
+        )
+    }
+}
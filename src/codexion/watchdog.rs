@@ -0,0 +1,207 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    time::Duration,
+};
+
+use crate::codexion::dongle::DongleSnapshot;
+
+/// Times a coder may be passed over on one dongle before he's reported starving
+pub const STARVATION_PASSES: u32 = 3;
+
+/// Who waits for whom, from every dongle's holder and waiting line
+#[derive(Clone, Debug)]
+pub struct WaitForGraph {
+    pub dongles: Vec<DongleSnapshot>,
+}
+
+/// An edge of the wait-for graph, `waiter` can't go on before `blocker` does
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WaitsFor {
+    pub waiter: u32,
+    pub blocker: u32,
+    pub dongle_id: u32,
+}
+
+/// Keeps what was already reported, so each problem is reported once
+#[derive(Default)]
+pub struct Watchdog {
+    /// edges of the previous check, a cycle must be in two checks in a row
+    previous_edges: BTreeSet<WaitsFor>,
+    reported_cycles: BTreeSet<Vec<u32>>,
+    reported_starving: BTreeSet<(u32, u32)>,
+}
+
+impl WaitForGraph {
+    /// A waiter waits for the holder, and for everyone before him in line
+    pub fn edges(&self) -> BTreeSet<WaitsFor> {
+        let mut edges = BTreeSet::new();
+
+        for dongle in &self.dongles {
+            for (position, &waiter) in dongle.waiting.iter().enumerate() {
+                let ahead = dongle.waiting[..position].iter().copied();
+                for blocker in dongle.holder.into_iter().chain(ahead) {
+                    if blocker != waiter {
+                        edges.insert(WaitsFor {
+                            waiter,
+                            blocker,
+                            dongle_id: dongle.id,
+                        });
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    /// Coders passed over at least [`STARVATION_PASSES`] times, with the dongle
+    pub fn starving(&self) -> Vec<(u32, u32)> {
+        self.dongles
+            .iter()
+            .flat_map(|dongle| {
+                dongle
+                    .passed_over
+                    .iter()
+                    .filter(|&&(_, count)| count >= STARVATION_PASSES)
+                    .map(|&(coder_id, _)| (coder_id, dongle.id))
+            })
+            .collect()
+    }
+
+    /// Graphviz graph of coders and dongles, the cycle and the starving
+    /// coders highlighted
+    pub fn to_dot(&self, cycle: &[u32], starving: &[(u32, u32)]) -> String {
+        let mut dot = String::from("digraph wait_for {\n    rankdir=LR;\n");
+        let coders: BTreeSet<u32> = self
+            .dongles
+            .iter()
+            .flat_map(|dongle| {
+                dongle
+                    .holder
+                    .into_iter()
+                    .chain(dongle.waiting.iter().copied())
+            })
+            .collect();
+
+        for coder_id in coders {
+            let color = if cycle.contains(&coder_id) {
+                ", color=red, fontcolor=red"
+            } else if starving.iter().any(|&(id, _)| id == coder_id) {
+                ", color=orange, fontcolor=orange"
+            } else {
+                ""
+            };
+            let _ = writeln!(dot, "    coder_{coder_id} [shape=ellipse{color}];");
+        }
+        for dongle in &self.dongles {
+            if dongle.holder.is_none() && dongle.waiting.is_empty() {
+                continue;
+            }
+            let _ = writeln!(dot, "    dongle_{} [shape=box];", dongle.id);
+            if let Some(holder) = dongle.holder {
+                let _ = writeln!(
+                    dot,
+                    "    dongle_{} -> coder_{holder} [label=\"held by\"];",
+                    dongle.id
+                );
+            }
+            for (position, coder_id) in dongle.waiting.iter().enumerate() {
+                let passed_over = dongle
+                    .passed_over
+                    .iter()
+                    .find(|&&(id, _)| id == *coder_id)
+                    .map_or(0, |&(_, count)| count);
+                let _ = writeln!(
+                    dot,
+                    "    coder_{coder_id} -> dongle_{} [label=\"#{} passed over {passed_over}\", style=dashed];",
+                    dongle.id,
+                    position + 1
+                );
+            }
+        }
+        dot.push('}');
+        dot
+    }
+}
+
+/// Coders of a cycle in the edges, from the lowest id, if there's one
+pub fn find_cycle(edges: &BTreeSet<WaitsFor>) -> Option<Vec<u32>> {
+    let mut next: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for edge in edges {
+        next.entry(edge.waiter).or_default().push(edge.blocker);
+    }
+
+    // depth first search, a coder on the current path seen again closes a cycle
+    let mut done = BTreeSet::new();
+    for &start in next.keys() {
+        let mut path = vec![start];
+        let mut branches = vec![next[&start].clone()];
+
+        while let Some(branch) = branches.last_mut() {
+            let Some(coder_id) = branch.pop() else {
+                done.insert(path.pop().unwrap_or_default());
+                branches.pop();
+                continue;
+            };
+            if let Some(position) = path.iter().position(|&id| id == coder_id) {
+                let mut cycle = path[position..].to_vec();
+                let lowest = cycle.iter().enumerate().min_by_key(|&(_, id)| *id);
+                let lowest = lowest.map_or(0, |(idx, _)| idx);
+                cycle.rotate_left(lowest);
+                return Some(cycle);
+            }
+            if !done.contains(&coder_id) {
+                path.push(coder_id);
+                branches.push(next.get(&coder_id).cloned().unwrap_or_default());
+            }
+        }
+    }
+    None
+}
+
+impl Watchdog {
+    /// Report the new deadlocks and starving coders of the graph on stderr
+    pub fn check(&mut self, graph: &WaitForGraph, elapsed: Duration) {
+        let edges = graph.edges();
+        // a cycle made of edges seen twice in a row isn't a torn snapshot
+        let lasting: BTreeSet<WaitsFor> =
+            edges.intersection(&self.previous_edges).copied().collect();
+        let cycle = find_cycle(&lasting);
+        self.previous_edges = edges;
+
+        let starving = graph.starving();
+        self.reported_starving
+            .retain(|coder_dongle| starving.contains(coder_dongle));
+        let new_starving: Vec<(u32, u32)> = starving
+            .iter()
+            .copied()
+            .filter(|coder_dongle| !self.reported_starving.contains(coder_dongle))
+            .collect();
+
+        let new_cycle = cycle.filter(|cycle| !self.reported_cycles.contains(cycle));
+        if new_cycle.is_none() && new_starving.is_empty() {
+            return;
+        }
+
+        let at = elapsed.as_millis();
+        if let Some(cycle) = &new_cycle {
+            let path: Vec<String> = cycle
+                .iter()
+                .chain(cycle.first())
+                .map(|coder_id| format!("coder_{coder_id}"))
+                .collect();
+            eprintln!("watchdog: {at} ms: deadlock: {}", path.join(" -> "));
+            self.reported_cycles.insert(cycle.clone());
+        }
+        for &(coder_id, dongle_id) in &new_starving {
+            eprintln!(
+                "watchdog: {at} ms: coder_{coder_id} was passed over {STARVATION_PASSES} times or more on dongle_{dongle_id}"
+            );
+            self.reported_starving.insert((coder_id, dongle_id));
+        }
+        eprintln!(
+            "{}",
+            graph.to_dot(new_cycle.as_deref().unwrap_or_default(), &starving)
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::{f64::consts::TAU, time::Duration};
+
+use crate::{
+    args::{Args, ArgsError},
+    rng::Rng,
+};
+
+/// How long a phase or a cooldown lasts, drawn again every time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    Constant(Duration),
+    /// anything from `min` to `max`, equally likely
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    /// cut at zero
+    Normal {
+        mean: Duration,
+        std_dev: Duration,
+    },
+    Exponential {
+        mean: Duration,
+    },
+    /// its logarithm is normal, it's never negative and has a long tail
+    LogNormal {
+        mean: Duration,
+        std_dev: Duration,
+    },
+}
+
+impl Distribution {
+    /// A duration (`200ms`), or `uniform:MIN:MAX`, `normal:MEAN:STDDEV`,
+    /// `exponential:MEAN` or `lognormal:MEAN:STDDEV` with durations as parameters
+    pub(crate) fn parse(value: &str, argument: &'static str) -> Result<Self, ArgsError> {
+        let invalid = || ArgsError::InvalidDistribution {
+            argument,
+            value: value.to_string(),
+        };
+        let Some((name, parameters)) = value.split_once(':') else {
+            return Ok(Distribution::Constant(Args::parse_duration(
+                value, argument,
+            )?));
+        };
+        let parameters = parameters
+            .split(':')
+            .map(|parameter| Args::parse_duration(parameter, argument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let distribution = match (name, parameters.as_slice()) {
+            ("constant", &[duration]) => Distribution::Constant(duration),
+            ("uniform", &[min, max]) if min <= max => Distribution::Uniform { min, max },
+            ("normal", &[mean, std_dev]) => Distribution::Normal { mean, std_dev },
+            ("exponential" | "exp", &[mean]) => Distribution::Exponential { mean },
+            ("lognormal", &[mean, std_dev]) => Distribution::LogNormal { mean, std_dev },
+            _ => return Err(invalid()),
+        };
+        Ok(distribution)
+    }
+
+    pub fn is_constant(&self) -> bool {
+        matches!(self, Distribution::Constant(_))
+    }
+
+    /// What it gives on average, for what has to be planned ahead
+    pub fn mean(&self) -> Duration {
+        match *self {
+            Distribution::Constant(duration) => duration,
+            Distribution::Uniform { min, max } => (min + max) / 2,
+            Distribution::Normal { mean, .. }
+            | Distribution::Exponential { mean }
+            | Distribution::LogNormal { mean, .. } => mean,
+        }
+    }
+
+    /// Draw a duration, constants don't use the generator
+    pub(crate) fn sample(&self, rng: &mut Rng) -> Duration {
+        let seconds = match *self {
+            Distribution::Constant(duration) => return duration,
+            Distribution::Uniform { min, max } => {
+                min.as_secs_f64() + (max - min).as_secs_f64() * rng.unit()
+            }
+            Distribution::Normal { mean, std_dev } => {
+                mean.as_secs_f64() + std_dev.as_secs_f64() * standard_normal(rng)
+            }
+            Distribution::Exponential { mean } => -mean.as_secs_f64() * (1.0 - rng.unit()).ln(),
+            Distribution::LogNormal { mean, std_dev } => {
+                let mean = mean.as_secs_f64();
+                if mean == 0.0 {
+                    return Duration::ZERO;
+                }
+                // parameters of the underlying normal, from the mean and deviation wanted
+                let variance = (1.0 + (std_dev.as_secs_f64() / mean).powi(2)).ln();
+                let mu = mean.ln() - variance / 2.0;
+                (mu + variance.sqrt() * standard_normal(rng)).exp()
+            }
+        };
+        Duration::from_secs_f64(seconds.max(0.0))
+    }
+}
+
+impl From<Duration> for Distribution {
+    fn from(duration: Duration) -> Self {
+        Distribution::Constant(duration)
+    }
+}
+
+/// Box-Muller transform
+fn standard_normal(rng: &mut Rng) -> f64 {
+    // in (0, 1], so the logarithm is finite
+    let radius = 1.0 - rng.unit();
+    let angle = rng.unit();
+    (-2.0 * radius.ln()).sqrt() * (TAU * angle).cos()
+}
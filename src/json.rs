@@ -0,0 +1,293 @@
+use std::{error::Error, fmt::Display, iter::Peekable, str::Chars};
+
+/// Minimal JSON document, enough for the files this program reads back
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// keys keep the order they had in the document
+    Object(Vec<(String, Value)>),
+}
+
+#[derive(Debug)]
+pub struct JsonError {
+    pub position: usize,
+    pub message: &'static str,
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: usize,
+}
+
+impl Value {
+    pub fn parse(text: &str) -> Result<Self, JsonError> {
+        let mut parser = Parser {
+            chars: text.chars().peekable(),
+            position: 0,
+        };
+
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.peek() {
+            None => Ok(value),
+            Some(_) => Err(parser.error("trailing characters")),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as u64)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<char> {
+        self.position += 1;
+        self.chars.next()
+    }
+
+    fn error(&self, message: &'static str) -> JsonError {
+        JsonError {
+            position: self.position,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.chars.peek() {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, JsonError> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Value::String),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('n') => self.keyword("null", Value::Null),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value, JsonError> {
+        for expected in keyword.chars() {
+            if self.next() != Some(expected) {
+                return Err(self.error("invalid keyword"));
+            }
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let mut literal = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9') {
+                break;
+            }
+            literal.push(c);
+            self.next();
+        }
+        literal
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('/') => string.push('/'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => {
+                        let code: String = (0..4).filter_map(|_| self.next()).collect();
+                        let c = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error("invalid unicode escape"))?;
+                        string.push(c);
+                    }
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(c) => string.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, JsonError> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.next();
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(values)),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, JsonError> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.next();
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::Object(entries)),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at character {}", self.message, self.position)
+    }
+}
+
+impl Error for JsonError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> &'static str {
+        Value::parse(text)
+            .expect_err("the JSON should be invalid")
+            .message
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            Value::parse(r#""a\"b\\c\/d\b\f\n\r\t""#).unwrap(),
+            Value::String("a\"b\\c/d\u{8}\u{c}\n\r\t".to_string())
+        );
+        assert_eq!(
+            Value::parse(r#""\u00e9\u0041""#).unwrap(),
+            Value::String("éA".to_string())
+        );
+        assert_eq!(error(r#""\x""#), "invalid escape");
+        assert_eq!(error(r#""\u12g4""#), "invalid unicode escape");
+    }
+
+    #[test]
+    fn nested_arrays_and_objects() {
+        let value =
+            Value::parse(r#" { "coders": [ {"id": 1, "dongles": [1, 2]}, null ], "ok": true } "#)
+                .unwrap();
+        let coders = value.get("coders").and_then(Value::as_array).unwrap();
+        assert_eq!(coders.len(), 2);
+        assert_eq!(coders[0].get("id").and_then(Value::as_u64), Some(1));
+        assert_eq!(
+            coders[0].get("dongles"),
+            Some(&Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]))
+        );
+        assert_eq!(coders[1], Value::Null);
+        assert_eq!(value.get("ok"), Some(&Value::Bool(true)));
+        assert_eq!(
+            Value::parse("[[], {}]").unwrap().as_array().map(<[_]>::len),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Value::parse("-1.5e2").unwrap(), Value::Number(-150.0));
+        assert_eq!(Value::parse("2.5").unwrap().as_u64(), None);
+        assert_eq!(Value::parse("-3").unwrap().as_u64(), None);
+        assert_eq!(error("1.2.3"), "invalid number");
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(error(""), "unexpected end of input");
+        assert_eq!(error("[1,"), "unexpected end of input");
+        assert_eq!(error("[1, 2"), "expected ',' or ']'");
+        assert_eq!(error("[1 2]"), "expected ',' or ']'");
+        assert_eq!(error(r#"{"a": 1 "b": 2}"#), "expected ',' or '}'");
+        assert_eq!(error(r#""open"#), "unterminated string");
+        assert_eq!(error("nul"), "invalid keyword");
+        assert_eq!(error("{} {}"), "trailing characters");
+        assert_eq!(error("@"), "unexpected character");
+    }
+}
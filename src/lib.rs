@@ -0,0 +1,40 @@
+//! Coders sitting in a ring, sharing one USB dongle with each neighbour, or
+//! on any other [`Topology`] of coders and dongles.
+//!
+//! A coder needs all of his dongles to compile, then he debugs and refactors,
+//! and he burns out if he goes `time_to_burnout` without compiling. Dongles are
+//! handed out by a [`Scheduler`], and every event of a run can be followed
+//! with an [`Observer`].
+
+pub mod analyze;
+pub mod args;
+mod clock;
+pub mod codexion;
+pub mod distribution;
+mod json;
+pub mod logging;
+pub mod replay;
+pub mod report;
+mod rng;
+pub mod scenario;
+pub mod sweep;
+mod toml;
+pub mod topology;
+pub mod trace;
+pub mod verify;
+
+pub use crate::{
+    args::{
+        Args, ArgsBuilder, ArgsError, Backend, ClockMode, CoderOverride, CoderProfile, LogFormat,
+        SchedulerKind,
+    },
+    codexion::{
+        Codexion, SimulationOutcome,
+        scheduler::{Aging, Edf, Fifo, Llf, Lottery, Request, RoundRobin, Scheduler},
+        stats::Statistics,
+    },
+    distribution::Distribution,
+    logging::{Event, EventKind, Observer},
+    topology::{Graph, Topology},
+    trace::Trace,
+};
@@ -1,94 +1,250 @@
-use std::{sync::Mutex, time::Instant};
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
-pub struct Logging {
+use crate::{
+    args::LogFormat,
+    clock::{Clock, millis},
+};
+
+pub(crate) struct Logging {
     pub start_time_lock: Mutex<Instant>,
+    sequence: AtomicU64,
+    /// once a coder burned out or the run was interrupted, nothing else gets logged
+    closed: AtomicBool,
+    clock: Arc<Clock>,
+    format: LogFormat,
+    /// durations are drawn from distributions, so they're worth logging
+    random_durations: bool,
+    observers: Mutex<Vec<Arc<dyn Observer>>>,
+}
+
+/// Something that happened during a run, as it's logged
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub sequence: u64,
+    /// time since the start of the run
+    pub elapsed: Duration,
+    pub coder_id: u32,
+    pub kind: EventKind,
+    /// how long the phase or the dongle's cooldown was drawn to last, only
+    /// when durations are random
+    pub duration: Option<Duration>,
+}
+
+/// Gets every event of a run, in logging order.
+///
+/// Observers are called while the log is locked, so they must not block for long
+/// and must not call back into the codexion.
+pub trait Observer: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+const RESET: &str = "\x1b[0m";
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const REVERSE: &str = "\x1b[7m";
+
+const FG_BLACK: &str = "\x1b[30m";
+const FG_RED: &str = "\x1b[31m";
+const FG_GREEN: &str = "\x1b[32m";
+const FG_YELLOW: &str = "\x1b[33m";
+const FG_BLUE: &str = "\x1b[34m";
+const FG_MAGENTA: &str = "\x1b[35m";
+const FG_CYAN: &str = "\x1b[36m";
+const FG_WHITE: &str = "\x1b[37m";
+
+const BG_RED: &str = "\x1b[41m";
+const BG_BLUE: &str = "\x1b[44m";
+const BG_WHITE: &str = "\x1b[47m";
+
+/// Everything that can happen to a coder, in the order of a compile cycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Acquire { dongle_id: u32 },
+    Compile,
+    Release { dongle_id: u32 },
+    Debug,
+    Refactor,
+    Burnout,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Acquire { .. } => "acquire",
+            EventKind::Compile => "compile",
+            EventKind::Release { .. } => "release",
+            EventKind::Debug => "debug",
+            EventKind::Refactor => "refactor",
+            EventKind::Burnout => "burnout",
+        }
+    }
+
+    /// Read back an event written by [`EventKind::name`]
+    pub fn from_name(name: &str, dongle_id: Option<u32>) -> Option<Self> {
+        match name {
+            "acquire" => Some(EventKind::Acquire {
+                dongle_id: dongle_id?,
+            }),
+            "compile" => Some(EventKind::Compile),
+            "release" => Some(EventKind::Release {
+                dongle_id: dongle_id?,
+            }),
+            "debug" => Some(EventKind::Debug),
+            "refactor" => Some(EventKind::Refactor),
+            "burnout" => Some(EventKind::Burnout),
+            _ => None,
+        }
+    }
+
+    pub fn dongle_id(&self) -> Option<u32> {
+        match self {
+            EventKind::Acquire { dongle_id } | EventKind::Release { dongle_id } => Some(*dongle_id),
+            _ => None,
+        }
+    }
 }
 
-const RESET: &'static str = "\x1b[0m";
-
-const BOLD: &'static str = "\x1b[1m";
-const DIM: &'static str = "\x1b[2m";
-const REVERSE: &'static str = "\x1b[7m";
-
-const FG_BLACK: &'static str = "\x1b[30m";
-const FG_RED: &'static str = "\x1b[31m";
-const FG_GREEN: &'static str = "\x1b[32m";
-const FG_YELLOW: &'static str = "\x1b[33m";
-const FG_BLUE: &'static str = "\x1b[34m";
-const FG_MAGENTA: &'static str = "\x1b[35m";
-const FG_CYAN: &'static str = "\x1b[36m";
-const FG_WHITE: &'static str = "\x1b[37m";
-
-const BG_BLACK: &'static str = "\x1b[40m";
-const BG_RED: &'static str = "\x1b[41m";
-const BG_GREEN: &'static str = "\x1b[42m";
-const BG_YELLOW: &'static str = "\x1b[43m";
-const BG_BLUE: &'static str = "\x1b[44m";
-const BG_MAGENTA: &'static str = "\x1b[45m";
-const BG_CYAN: &'static str = "\x1b[46m";
-const BG_WHITE: &'static str = "\x1b[47m";
+impl Event {
+    /// One line of the JSON Lines log
+    pub fn to_json(&self) -> String {
+        let dongle = match self.kind.dongle_id() {
+            Some(dongle_id) => dongle_id.to_string(),
+            None => "null".to_string(),
+        };
+
+        let duration = match self.duration {
+            Some(duration) => format!(",\"duration_us\":{}", duration.as_micros()),
+            None => String::new(),
+        };
+
+        format!(
+            "{{\"seq\":{},\"ms\":{},\"us\":{},\"coder\":{},\"event\":\"{}\",\"dongle\":{dongle}{duration}}}",
+            self.sequence,
+            self.elapsed.as_millis(),
+            self.elapsed.as_micros(),
+            self.coder_id,
+            self.kind.name()
+        )
+    }
+}
 
 impl Logging {
-    pub fn new() -> Self {
+    pub fn new(clock: Arc<Clock>, format: LogFormat, random_durations: bool) -> Self {
         Self {
-            start_time_lock: Mutex::new(Instant::now()),
+            start_time_lock: Mutex::new(clock.now()),
+            sequence: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            clock,
+            format,
+            random_durations,
+            observers: Mutex::new(Vec::new()),
         }
     }
 
-    pub fn compile(&self, coder_id: u32) {
-        let start_time = self.start_time_lock.lock().unwrap();
-        println!(
-            "{}\t {FG_CYAN}{BG_BLUE}{BOLD} COMPILING {RESET}",
-            self.time_id_prefix(*start_time, coder_id)
-        );
+    pub fn add_observer(&self, observer: Arc<dyn Observer>) {
+        self.observers.lock().unwrap().push(observer);
     }
 
-    pub fn debug(&self, coder_id: u32) {
-        let start_time = self.start_time_lock.lock().unwrap();
-        println!(
-            "{}\t {FG_YELLOW}{REVERSE} DEBUGGING {RESET}",
-            self.time_id_prefix(*start_time, coder_id)
-        );
+    pub fn compile(&self, coder_id: u32, time_to_compile: Duration) {
+        self.log(coder_id, EventKind::Compile, Some(time_to_compile));
     }
 
-    pub fn refactor(&self, coder_id: u32) {
-        let start_time = self.start_time_lock.lock().unwrap();
-        println!(
-            "{}\t {FG_MAGENTA}{REVERSE}REFACTORING{RESET}",
-            self.time_id_prefix(*start_time, coder_id)
-        );
+    pub fn debug(&self, coder_id: u32, time_to_debug: Duration) {
+        self.log(coder_id, EventKind::Debug, Some(time_to_debug));
+    }
+
+    pub fn refactor(&self, coder_id: u32, time_to_refactor: Duration) {
+        self.log(coder_id, EventKind::Refactor, Some(time_to_refactor));
     }
 
     pub fn acquire(&self, coder_id: u32, dongle_id: u32) {
-        let start_time = self.start_time_lock.lock().unwrap();
-        println!(
-            "{}\t {DIM}ACQUIRED dongle_{dongle_id}{RESET}",
-            self.time_id_prefix(*start_time, coder_id)
-        );
+        self.log(coder_id, EventKind::Acquire { dongle_id }, None);
     }
 
-    pub fn release(&self, coder_id: u32, dongle_id: u32) {
-        let start_time = self.start_time_lock.lock().unwrap();
-        println!(
-            "{}\t {DIM}RELEASED dongle_{dongle_id}{RESET}",
-            self.time_id_prefix(*start_time, coder_id)
-        );
+    /// `cooldown` is the one the dongle starts with this release
+    pub fn release(&self, coder_id: u32, dongle_id: u32, cooldown: Duration) {
+        self.log(coder_id, EventKind::Release { dongle_id }, Some(cooldown));
     }
 
     pub fn burnout(&self, coder_id: u32) {
+        self.log(coder_id, EventKind::Burnout, None);
+    }
+
+    /// Log nothing more, the run was stopped
+    pub fn close(&self) {
+        let _start_time = self.start_time_lock.lock().unwrap();
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    fn log(&self, coder_id: u32, kind: EventKind, duration: Option<Duration>) {
+        // holding the start time lock keeps sequence numbers in printing order
         let start_time = self.start_time_lock.lock().unwrap();
-        println!(
-            "{}\t {FG_WHITE}{BG_RED}{BOLD} BURNED OUT {RESET}",
-            self.time_id_prefix(*start_time, coder_id)
-        );
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        if kind == EventKind::Burnout {
+            self.closed.store(true, Ordering::Relaxed);
+        }
+
+        let event = Event {
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            elapsed: self.clock.now().saturating_duration_since(*start_time),
+            coder_id,
+            kind,
+            duration: duration.filter(|_| self.random_durations),
+        };
+
+        match self.format {
+            LogFormat::Text => println!(
+                "{}\t {}{}",
+                self.time_id_prefix(event.elapsed.as_millis(), coder_id),
+                Self::event_label(kind),
+                Self::duration_label(kind, event.duration)
+            ),
+            LogFormat::Json => println!("{}", event.to_json()),
+            LogFormat::Quiet | LogFormat::Tui => (),
+        }
+
+        for observer in self.observers.lock().unwrap().iter() {
+            observer.on_event(&event);
+        }
+    }
+
+    fn event_label(event: EventKind) -> String {
+        match event {
+            EventKind::Compile => format!("{FG_CYAN}{BG_BLUE}{BOLD} COMPILING {RESET}"),
+            EventKind::Debug => format!("{FG_YELLOW}{REVERSE} DEBUGGING {RESET}"),
+            EventKind::Refactor => format!("{FG_MAGENTA}{REVERSE}REFACTORING{RESET}"),
+            EventKind::Acquire { dongle_id } => format!("{DIM}ACQUIRED dongle_{dongle_id}{RESET}"),
+            EventKind::Release { dongle_id } => format!("{DIM}RELEASED dongle_{dongle_id}{RESET}"),
+            EventKind::Burnout => format!("{FG_WHITE}{BG_RED}{BOLD} BURNED OUT {RESET}"),
+        }
+    }
+
+    fn duration_label(event: EventKind, duration: Option<Duration>) -> String {
+        let Some(duration) = duration else {
+            return String::new();
+        };
+        let millis = millis(duration);
+
+        match event {
+            EventKind::Release { .. } => format!(" {DIM}(cooldown {millis:.1} ms){RESET}"),
+            _ => format!(" {DIM}({millis:.1} ms){RESET}"),
+        }
     }
 
-    fn time_id_prefix(&self, start_time: Instant, coder_id: u32) -> String {
-        const COLOR_CYCLE: [&'static str; 7] = [
+    fn time_id_prefix(&self, current_time: u128, coder_id: u32) -> String {
+        const COLOR_CYCLE: [&str; 7] = [
             FG_RED, FG_GREEN, FG_YELLOW, FG_BLUE, FG_MAGENTA, FG_CYAN, FG_BLACK,
         ];
 
-        let current_time = start_time.elapsed().as_millis();
         let coder_id_color = COLOR_CYCLE[coder_id as usize % COLOR_CYCLE.len()];
 
         format!(
@@ -1,49 +1,199 @@
-mod args;
-mod codexion;
-mod logging;
-use crate::{args::Args, codexion::Codexion};
-use std::env::args;
-
-fn main() {
-    let args: Args = match args().try_into() {
+use codexion_rs::{
+    Args, Codexion, SimulationOutcome, analyze, codexion::signals, report, sweep, verify,
+};
+use std::{env::args, process::ExitCode};
+
+/// every coder finished his compiles, or the verified log is valid
+const EXIT_COMPLETED: u8 = 0;
+/// a coder burned out, or the verified log breaks a rule
+const EXIT_BURNED_OUT: u8 = 1;
+/// the arguments or the configuration are invalid
+const EXIT_CONFIG_ERROR: u8 = 2;
+/// a signal stopped the run, the exit status is this plus its number, like a shell's
+const EXIT_SIGNAL_BASE: u8 = 128;
+
+fn main() -> ExitCode {
+    let program_args: Vec<String> = args().collect();
+    signals::install();
+
+    if program_args.get(1).map(String::as_str) == Some("verify") {
+        return match verify::run(&program_args[2..]) {
+            Ok(true) => ExitCode::from(EXIT_COMPLETED),
+            Ok(false) => ExitCode::from(EXIT_BURNED_OUT),
+            Err(err) => {
+                eprintln!("{err}");
+                if err.is_usage_error() {
+                    print_usage();
+                }
+                ExitCode::from(EXIT_CONFIG_ERROR)
+            }
+        };
+    }
+
+    if program_args.get(1).map(String::as_str) == Some("analyze") {
+        return match analyze::run(&program_args[2..]) {
+            Ok(true) => ExitCode::from(EXIT_COMPLETED),
+            Ok(false) => ExitCode::from(EXIT_BURNED_OUT),
+            Err(err) => {
+                eprintln!("{err}");
+                print_usage();
+                ExitCode::from(EXIT_CONFIG_ERROR)
+            }
+        };
+    }
+
+    if program_args.get(1).map(String::as_str) == Some("sweep") {
+        return match sweep::run(&program_args[2..]) {
+            Ok(()) => ExitCode::from(EXIT_COMPLETED),
+            Err(err) => {
+                eprintln!("{err}");
+                print_usage();
+                ExitCode::from(EXIT_CONFIG_ERROR)
+            }
+        };
+    }
+
+    if program_args.get(1).map(String::as_str) == Some("report") {
+        return match report::run(&program_args[2..]) {
+            Ok(outcome) => exit_code(outcome),
+            Err(err) => {
+                eprintln!("{err}");
+                print_usage();
+                ExitCode::from(EXIT_CONFIG_ERROR)
+            }
+        };
+    }
+
+    let args: Args = match program_args.try_into() {
         Ok(args) => args,
         Err(err) => {
             eprintln!("{err}");
-            return print_usage();
+            print_usage();
+            return ExitCode::from(EXIT_CONFIG_ERROR);
         }
     };
 
-    dbg!(&args);
-
     let codexion = Codexion::new(args);
-    codexion.start();
+    exit_code(codexion.start())
+}
+
+fn exit_code(outcome: SimulationOutcome) -> ExitCode {
+    match outcome {
+        SimulationOutcome::Completed => ExitCode::from(EXIT_COMPLETED),
+        SimulationOutcome::BurnedOut { .. } => ExitCode::from(EXIT_BURNED_OUT),
+        SimulationOutcome::Interrupted { signal, .. } => {
+            ExitCode::from(EXIT_SIGNAL_BASE + signal.number() as u8)
+        }
+    }
 }
 
 fn print_usage() {
-    const FG_GREEN: &'static str = "\x1b[32m";
-    const FG_BLUE: &'static str = "\x1b[34m";
-    const BOLD: &'static str = "\x1b[1m";
-    const DIM: &'static str = "\x1b[2m";
-    const RESET: &'static str = "\x1b[0m";
+    const FG_GREEN: &str = "\x1b[32m";
+    const FG_BLUE: &str = "\x1b[34m";
+    const BOLD: &str = "\x1b[1m";
+    const DIM: &str = "\x1b[2m";
+    const RESET: &str = "\x1b[0m";
 
     eprintln!("\
 Usage:
-    {FG_BLUE}{BOLD}codexion-rs{RESET}  {BOLD}number_of_coders time_to_burnout time_to_compile time_to_debug time_to_refactor number_of_compiles_required dongle_cooldown scheduler{RESET}
+    {FG_BLUE}{BOLD}codexion-rs{RESET}  [{BOLD}--coders{RESET} n] [{BOLD}--burnout{RESET} duration] [{BOLD}--compile{RESET} duration] [{BOLD}--debug{RESET} duration] [{BOLD}--refactor{RESET} duration] [{BOLD}--compiles{RESET} n] [{BOLD}--cooldown{RESET} duration] [{BOLD}--scheduler{RESET} name] [{BOLD}--topology{RESET} layout] [{BOLD}--backend{RESET} name] [{BOLD}--scenario{RESET} file] [{BOLD}--post-mortem{RESET} file] [{BOLD}--seed{RESET} n] [{BOLD}--trace-out{RESET} file] [{BOLD}--record{RESET} file] [{BOLD}--replay{RESET} file] [options]
+    {FG_BLUE}{BOLD}codexion-rs{RESET}  {BOLD}number_of_coders time_to_burnout time_to_compile time_to_debug time_to_refactor number_of_compiles_required dongle_cooldown scheduler{RESET} [{BOLD}--virtual{RESET}] [{BOLD}--json{RESET}|{BOLD}--quiet{RESET}|{BOLD}--tui{RESET}] [{BOLD}--stats{RESET}] [{BOLD}--interactive{RESET}]
+    {FG_BLUE}{BOLD}codexion-rs sweep{RESET}  [{BOLD}--coders{RESET} 2..50] [{BOLD}--burnout{RESET} 300..1000:50] [{BOLD}--scheduler{RESET} fifo,edf] [...] [{BOLD}--runs{RESET} n] [{BOLD}--jobs{RESET} n] [{BOLD}--out{RESET} file.csv]
+    {FG_BLUE}{BOLD}codexion-rs analyze{RESET}  [the arguments of a run]
+    {FG_BLUE}{BOLD}codexion-rs report{RESET}  [the arguments of a run] [{BOLD}--svg{RESET} file.svg] [{BOLD}--html{RESET} file.html]
+    {FG_BLUE}{BOLD}codexion-rs verify{RESET}  {BOLD}logfile{RESET} [{BOLD}--coders{RESET} n] [{BOLD}--topology{RESET} layout] [{BOLD}--cooldown{RESET} duration] [{BOLD}--burnout{RESET} duration] [{BOLD}--tolerance{RESET} duration]
+
+    {FG_GREEN}number_of_coders{RESET}, {FG_GREEN}--coders{RESET} ({DIM}u32, default 5{RESET}): number of coders and threads.
+
+    {FG_GREEN}time_to_burnout{RESET}, {FG_GREEN}--burnout{RESET} ({DIM}duration, default 800ms{RESET}): time until a coder burns out, if he doesn't compile.
+
+    {FG_GREEN}time_to_compile{RESET}, {FG_GREEN}--compile{RESET} ({DIM}duration, default 200ms{RESET}): time it takes to compile.
+
+    {FG_GREEN}time_to_debug{RESET}, {FG_GREEN}--debug{RESET} ({DIM}duration, default 200ms{RESET}): time it takes to debug.
+
+    {FG_GREEN}time_to_refactor{RESET}, {FG_GREEN}--refactor{RESET} ({DIM}duration, default 200ms{RESET}): time it takes to refactor.
+
+    {FG_GREEN}number_of_compiles_required{RESET}, {FG_GREEN}--compiles{RESET} ({DIM}u32, default 3{RESET}): number of successful compiles required for a coder to stop.
+
+    {FG_GREEN}dongle_cooldown{RESET}, {FG_GREEN}--cooldown{RESET} ({DIM}duration, default 10ms{RESET}): time it takes for a dongle to cooldown after being used.
+
+    {FG_GREEN}scheduler{RESET}, {FG_GREEN}--scheduler{RESET} ({DIM}default fifo{RESET}): scheduling strategy, who gets a dongle next.
+        {BOLD}fifo{RESET}: First In First Out.
+        {BOLD}edf{RESET}: Earliest Deadline First, the coder closest to burning out.
+        {BOLD}llf{RESET}: Least Laxity First, the coder closest to burning out once his compile time is counted.
+        {BOLD}lottery[:SEED]{RESET}: a coder in line drawn at random, from SEED or else --seed, on a stream of its own for each dongle.
+        {BOLD}round-robin{RESET}: the next coder by id after the last one served.
+        {BOLD}aging{RESET}: the lowest coder id first, every turn a coder loses in line raises his priority.
+
+    {FG_GREEN}--topology{RESET} ({DIM}default ring{RESET}): which dongles each coder needs, always taken in dongle order so no layout can deadlock.
+        {BOLD}ring{RESET}: one dongle between each pair of neighbours.
+        {BOLD}star{RESET}: a dongle shared by everyone, plus one of his own.
+        {BOLD}complete{RESET}: every coder needs every dongle.
+        {BOLD}grid:COLUMNS{RESET}: coders on a grid, one dongle between each pair of neighbours.
+        {BOLD}random:DEGREE[:SEED]{RESET}: DEGREE random dongles per coder, each needed by DEGREE coders.
+    A scenario file can also give a coder his own {BOLD}dongles{RESET} list.
+
+    {FG_GREEN}--backend{RESET} ({DIM}locks/chandy-misra/arbiter, default locks{RESET}): how coders share dongles.
+        {BOLD}locks{RESET}: one dongle at a time, each dongle has its own scheduler.
+        {BOLD}chandy-misra{RESET}: passed between neighbours over channels with the Chandy–Misra protocol (real clock only, each dongle needed by two coders at most, the scheduler isn't used).
+        {BOLD}arbiter{RESET}: all of a coder's dongles at once, from one waiting line for everyone ordered by the scheduler.
+
+    Durations are a number with a {BOLD}us{RESET}, {BOLD}ms{RESET}, {BOLD}s{RESET} or {BOLD}m{RESET} suffix ({DIM}1.2s{RESET}), plain numbers are millis.
+    {BOLD}--compile{RESET}, {BOLD}--debug{RESET}, {BOLD}--refactor{RESET} and {BOLD}--cooldown{RESET} also take a distribution, drawn again every time:
+        {BOLD}uniform:MIN:MAX{RESET}, {BOLD}normal:MEAN:STDDEV{RESET}, {BOLD}exponential:MEAN{RESET} or {BOLD}lognormal:MEAN:STDDEV{RESET} ({DIM}normal:200ms:50ms{RESET}).
+    Every value drawn is logged with its event.
+
+    {FG_GREEN}--seed{RESET} ({DIM}u64, default 0{RESET}): seed of the random durations, the same seed draws the same durations.
+
+    {FG_GREEN}--scenario{RESET} ({DIM}file.toml/file.json{RESET}): load the settings from a scenario file, with {BOLD}[[coder]]{RESET} and {BOLD}[[dongle]]{RESET} tables for per-coder durations and compiles, and per-dongle cooldowns. Arguments given after it override it.
+
+    {FG_GREEN}--virtual{RESET}: run on a virtual clock, events happen instantly and every run with the same arguments is identical.
+
+    {FG_GREEN}--json{RESET}: print one JSON object per event (JSON Lines) instead of coloured lines.
+
+    {FG_GREEN}--quiet{RESET}: don't print any event.
+
+    {FG_GREEN}--tui{RESET}: instead of the events, a live view redrawn in place: the phase of every coder, how long before he burns out and his compiles, and whether each dongle is held, cooling down or free, with its waiting line (real clock only).
+
+    {FG_GREEN}--stats{RESET}: print compiles, dongle waiting times, utilisation and fairness at the end of the run, on stderr with {BOLD}--json{RESET} so the log stays JSON Lines.
+
+    {FG_GREEN}--post-mortem{RESET} ({DIM}file, - for stdout{RESET}): when a coder burns out, write what every coder was doing, how long since he compiled, who holds and waits for each dongle, and the chain of coders the one who burned out was waiting on.
+
+    {FG_GREEN}--trace-out{RESET} ({DIM}file.json{RESET}): write the run in the Chrome Trace Event format, for {DIM}chrome://tracing{RESET} or Perfetto: a track per coder with his compiles, debugs, refactors and waits, a track per dongle with its holds and cooldowns, and an arrow from each release to the coder who was waiting for it.
+
+    {FG_GREEN}--record{RESET} ({DIM}file.jsonl{RESET}): write every grant of a dongle, which coder got it and when, one JSON object per line (locks backend only).
+
+    {FG_GREEN}--replay{RESET} ({DIM}file.jsonl{RESET}): hand every dongle to the coders of a {BOLD}--record{RESET}ed run in the same order, to reproduce a run the threads interleaved differently; the scheduler takes over once the recording runs out (locks backend only, same coders and dongles).
 
-    {FG_GREEN}number_of_coders{RESET} ({DIM}u32{RESET}): number of coders and threads.
+    {FG_GREEN}--watchdog{RESET}: watch who waits for whom during the run (locks backend only), and print the wait-for graph in DOT on stderr when coders wait on each other in a cycle, or a coder was passed over 3 times on a dongle.
 
-    {FG_GREEN}time_to_burnout{RESET} ({DIM}u64{RESET}): millis until a coder burns out, if he doesn't compile.
+    {FG_GREEN}--interactive{RESET}: read commands from stdin while the coders run, and answer on stderr (real clock and locks backend only).
+        {BOLD}pause{RESET}, {BOLD}resume{RESET}: hold every coder and dongle where they are, the time paused counts toward no burnout, cooldown or logged time.
+        {BOLD}status{RESET}: print what every coder and dongle is doing.
+        {BOLD}slow{RESET} coder factor: make every next phase of the coder last factor times as long ({DIM}slow 3 2{RESET}, {DIM}slow 3 0.5{RESET}).
+        {BOLD}kill{RESET} coder: the coder gives his dongles back and leaves, the others go on without him.
+        {BOLD}cooldown{RESET} dongle duration: draw the next cooldowns of the dongle from a duration or a distribution.
+        {BOLD}stop{RESET}: stop the run like Ctrl-C.
 
-    {FG_GREEN}time_to_compile{RESET} ({DIM}u64{RESET}): millis it takes to compile.
+    {FG_GREEN}verify{RESET}: check a run recorded with {BOLD}--json{RESET}: no dongle held twice, cooldowns respected, compiles only with all of his dongles, nothing after a burnout, burnout on time.
 
-    {FG_GREEN}time_to_debug{RESET} ({DIM}u64{RESET}): millis it takes to debug.
+    {FG_GREEN}sweep{RESET}: run every combination of the given values and write one CSV line per run: outcome, first burnout time, compiles and fairness.
+        Any argument that takes a value takes a list ({DIM}fifo,edf{RESET}) or a range {BOLD}FROM..TO[:STEP]{RESET} ({DIM}2..50{RESET}, {DIM}300ms..1s:50ms{RESET}), or both; switches and {BOLD}--scenario{RESET} apply to every run.
+        {BOLD}--runs{RESET} ({DIM}default 1{RESET}): runs of each combination, the run i uses the seed + i - 1.
+        {BOLD}--jobs{RESET} ({DIM}default the number of CPUs{RESET}): runs at the same time with {BOLD}--virtual{RESET}, real clock runs go one at a time so they don't slow each other down.
+        {BOLD}--out{RESET} ({DIM}default stdout{RESET}): file to write the CSV to.
 
-    {FG_GREEN}time_to_refactor{RESET} ({DIM}u64{RESET}): millis it takes to refactor.
+    {FG_GREEN}analyze{RESET}: before running, tell whether the arguments can avoid a burnout at all: the gaps between compiles no scheduler can avoid (own cycle, dongles shared, odd or even ring) and the minimum safe {BOLD}time_to_burnout{RESET}, then how FIFO and EDF do on the virtual clock.
 
-    {FG_GREEN}number_of_compiles_required{RESET} ({DIM}u32{RESET}): number of successful compiles required for a coder to stop.
+    {FG_GREEN}report{RESET}: run quietly, then write a Gantt chart of the run, a row per coder coloured by phase, a row per dongle with who held it and a red line at the burnout, and a single HTML page with the chart next to the statistics tables.
+        {BOLD}--svg{RESET} ({DIM}default report.svg{RESET}): file to write the chart to.
+        {BOLD}--html{RESET} ({DIM}default report.html{RESET}): file to write the page to.
 
-    {FG_GREEN}dongle_cooldown{RESET} ({DIM}u64{RESET}): millis it takes for a dongle to cooldown after being used.
+Exit status:
+    {BOLD}0{RESET} every coder did his compiles (or the verified log is valid, or the analyzed arguments can avoid a burnout), {BOLD}1{RESET} a coder burned out (or the log breaks a rule, or a burnout can't be avoided), {BOLD}2{RESET} invalid arguments, {BOLD}130{RESET} or {BOLD}143{RESET} stopped by SIGINT or SIGTERM (or {BOLD}130{RESET} by the {BOLD}stop{RESET} command).
 
-    {FG_GREEN}scheduler{RESET} ({DIM}fifo/edf{RESET}): scheduling strategy, FIFO (First In First Out), EDF (Earliest Deadline First)\
+Signals:
+    {BOLD}SIGINT{RESET} (Ctrl-C) or {BOLD}SIGTERM{RESET}: stop the coders, then print what every coder and dongle was doing and the statistics so far on stderr; a second one quits right away.
+    {BOLD}SIGUSR1{RESET}: print what every coder and dongle is doing on stderr, the run goes on.\
 ");
 }
@@ -0,0 +1,251 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{Debug, Display},
+    fs, io,
+    sync::Mutex,
+    time::Duration,
+};
+
+use crate::{
+    codexion::scheduler::{Request, Scheduler},
+    json::{JsonError, Value},
+    logging::{Event, EventKind, Observer},
+};
+
+/// A dongle handed to a coder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grant {
+    pub dongle_id: u32,
+    pub coder_id: u32,
+    /// time since the start of the run
+    pub elapsed: Duration,
+}
+
+/// Records every grant of a run, to write it once it's over
+#[derive(Default)]
+pub struct Recorder {
+    grants: Mutex<Vec<Grant>>,
+}
+
+/// The grants of a recorded run, to hand the dongles out in the same order
+#[derive(Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub path: String,
+    pub grants: Vec<Grant>,
+}
+
+/// Waiting line that gives each dongle to the coders of a recording, in
+/// their order, then lets its own scheduler decide once they're all served
+/// or once the run strays from the recording
+pub struct Replayed {
+    dongle_id: u32,
+    scheduling: Box<dyn Scheduler>,
+    /// coders still to be served, the next one first
+    order: VecDeque<u32>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    InvalidJson {
+        path: String,
+        line: usize,
+        source: JsonError,
+    },
+    InvalidGrant {
+        path: String,
+        line: usize,
+    },
+}
+
+impl Grant {
+    /// One line of a recording
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"dongle\":{},\"coder\":{},\"us\":{}}}",
+            self.dongle_id,
+            self.coder_id,
+            self.elapsed.as_micros()
+        )
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            dongle_id: value.get("dongle")?.as_u64()?.try_into().ok()?,
+            coder_id: value.get("coder")?.as_u64()?.try_into().ok()?,
+            elapsed: Duration::from_micros(value.get("us")?.as_u64()?),
+        })
+    }
+}
+
+impl Recorder {
+    /// One grant per line, in the order they were made
+    pub fn to_json_lines(&self) -> String {
+        self.grants
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|grant| grant.to_json() + "\n")
+            .collect()
+    }
+}
+
+impl Observer for Recorder {
+    /// A dongle's acquires are logged while it's held, so they come in the
+    /// order it was granted
+    fn on_event(&self, event: &Event) {
+        if let EventKind::Acquire { dongle_id } = event.kind {
+            self.grants.lock().unwrap().push(Grant {
+                dongle_id,
+                coder_id: event.coder_id,
+                elapsed: event.elapsed,
+            });
+        }
+    }
+}
+
+impl Recording {
+    /// Read a recording written with `--record`
+    pub fn load(path: &str) -> Result<Self, ReplayError> {
+        let text = fs::read_to_string(path).map_err(|source| ReplayError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        let mut grants = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value = Value::parse(line).map_err(|source| ReplayError::InvalidJson {
+                path: path.to_string(),
+                line: idx + 1,
+                source,
+            })?;
+            let grant = Grant::from_json(&value).ok_or_else(|| ReplayError::InvalidGrant {
+                path: path.to_string(),
+                line: idx + 1,
+            })?;
+            grants.push(grant);
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            grants,
+        })
+    }
+
+    /// Coders the dongle was granted to, in order
+    pub fn order_of(&self, dongle_id: u32) -> VecDeque<u32> {
+        self.grants
+            .iter()
+            .filter(|grant| grant.dongle_id == dongle_id)
+            .map(|grant| grant.coder_id)
+            .collect()
+    }
+}
+
+impl Debug for Recording {
+    /// Without the grants, a recording has thousands of them
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recording")
+            .field("path", &self.path)
+            .field("grants", &self.grants.len())
+            .finish()
+    }
+}
+
+impl Replayed {
+    pub fn new(dongle_id: u32, scheduling: Box<dyn Scheduler>, order: VecDeque<u32>) -> Self {
+        Self {
+            dongle_id,
+            scheduling,
+            order,
+        }
+    }
+
+    /// The recorded coder to serve next, once he's in line
+    fn next(&self) -> Option<u32> {
+        let &next = self.order.front()?;
+        self.scheduling.waiting().contains(&next).then_some(next)
+    }
+}
+
+impl Scheduler for Replayed {
+    fn enqueue(&mut self, request: Request) {
+        self.scheduling.enqueue(request);
+    }
+
+    fn peek(&self) -> Option<u32> {
+        if self.order.is_empty() {
+            return self.scheduling.peek();
+        }
+        // nobody else gets it while the next one isn't there yet
+        self.next()
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        if self.order.is_empty() {
+            return self.scheduling.pop();
+        }
+        let next = self.next()?;
+        self.order.pop_front();
+        self.scheduling.remove(next);
+        Some(next)
+    }
+
+    /// In the recorded run, a free dongle went to someone in line right away.
+    /// When it's free now with coders waiting but not the recorded one, the
+    /// run went another way and he may never come, so the scheduler takes over.
+    fn choose(&mut self) {
+        if let Some(&next) = self.order.front()
+            && self.next().is_none()
+            && !self.scheduling.waiting().is_empty()
+        {
+            eprintln!(
+                "replay: dongle {} is free but coder {next} isn't waiting for it as recorded, its scheduler decides from here",
+                self.dongle_id
+            );
+            self.order.clear();
+        }
+        self.scheduling.choose();
+    }
+
+    fn remove(&mut self, coder_id: u32) {
+        self.scheduling.remove(coder_id);
+    }
+
+    fn waiting(&self) -> Vec<u32> {
+        let mut line = self.scheduling.waiting();
+        if let Some(next) = self.next() {
+            line.retain(|&coder_id| coder_id != next);
+            line.insert(0, next);
+        }
+        line
+    }
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io { path, source } => {
+                write!(f, "Error: can't read recording '{path}': {source}")
+            }
+            ReplayError::InvalidJson { path, line, source } => {
+                write!(
+                    f,
+                    "Error: invalid JSON in recording '{path}' on line {line}: {source}"
+                )
+            }
+            ReplayError::InvalidGrant { path, line } => {
+                write!(f, "Error: line {line} of recording '{path}' is not a grant")
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {}
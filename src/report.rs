@@ -0,0 +1,470 @@
+use std::{
+    error::Error,
+    fmt::{Display, Write},
+    fs, io,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    args::{Args, ArgsError, Backend, ClockMode, LogFormat, SchedulerKind},
+    clock::millis,
+    codexion::{Codexion, SimulationOutcome, stats::Statistics},
+    distribution::Distribution,
+    logging::Observer,
+    topology::Topology,
+    trace::{SliceKind, Timeline, Trace, Track},
+};
+
+/// Where to write the chart and the page
+#[derive(Debug)]
+pub struct ReportOptions {
+    /// the arguments of the run, as given
+    pub run: Vec<String>,
+    pub svg: String,
+    pub html: String,
+}
+
+#[derive(Debug)]
+pub enum ReportError {
+    InvalidArgument(ArgsError),
+    MissingValue(String),
+    Io { path: String, source: io::Error },
+}
+
+/// Width of the coder and dongle names, left of the chart
+const LABEL_WIDTH: f64 = 90.0;
+/// Width of the time axis
+const PLOT_WIDTH: f64 = 1000.0;
+const ROW_HEIGHT: f64 = 22.0;
+const BAR_HEIGHT: f64 = 16.0;
+/// Above the first row, for the title
+const TOP: f64 = 30.0;
+/// Between the coder rows and the dongle rows
+const SECTION_GAP: f64 = 12.0;
+/// Below the last row, for the time axis and the legend
+const BOTTOM: f64 = 64.0;
+
+const WAITING: &str = "#c8c8c8";
+const COMPILE: &str = "#2f6fdf";
+const DEBUG: &str = "#e8b417";
+const REFACTOR: &str = "#c03fc0";
+const HELD: &str = "#1a9e8f";
+const COOLDOWN: &str = "#b9e6df";
+const BURNOUT: &str = "#d62828";
+
+impl TryFrom<&[String]> for ReportOptions {
+    type Error = ReportError;
+
+    /// `--svg` and `--html` are the report's, everything else is the run's
+    fn try_from(args: &[String]) -> Result<Self, Self::Error> {
+        let mut options = Self {
+            run: Vec::new(),
+            svg: "report.svg".to_string(),
+            html: "report.html".to_string(),
+        };
+        let mut args = args.iter();
+
+        while let Some(token) = args.next() {
+            let (name, inline_value) = match token.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (token.as_str(), None),
+            };
+            if !matches!(name, "--svg" | "--html") {
+                options.run.push(token.clone());
+                continue;
+            }
+            let value = match inline_value {
+                Some(value) => value,
+                None => args
+                    .next()
+                    .ok_or_else(|| ReportError::MissingValue(token.clone()))?
+                    .clone(),
+            };
+            match name {
+                "--svg" => options.svg = value,
+                _ => options.html = value,
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Run quietly and write its Gantt chart and its HTML report, returns how
+/// the run ended
+pub fn run(args: &[String]) -> Result<SimulationOutcome, ReportError> {
+    let options = ReportOptions::try_from(args)?;
+    let mut tokens = vec!["report".to_string()];
+    tokens.extend(options.run.iter().cloned());
+    let mut args = Args::try_from(tokens).map_err(ReportError::InvalidArgument)?;
+    args.log_format = LogFormat::Quiet;
+    args.stats = false;
+    args.interruption_summary = false;
+
+    let trace = Arc::new(Trace::new(&args));
+    let codexion = Codexion::new(args.clone());
+    codexion.observe(Arc::clone(&trace) as Arc<dyn Observer>);
+    let outcome = codexion.start();
+    let statistics = codexion.statistics();
+    let timeline = trace.timeline(statistics.duration);
+
+    let svg = gantt(&timeline);
+    write(&options.svg, &svg)?;
+    write(&options.html, &html(&args, outcome, &statistics, &svg))?;
+
+    println!(
+        "{}, wrote {} and {}",
+        outcome_line(outcome),
+        options.svg,
+        options.html
+    );
+    Ok(outcome)
+}
+
+fn write(path: &str, contents: &str) -> Result<(), ReportError> {
+    fs::write(path, contents).map_err(|source| ReportError::Io {
+        path: path.to_string(),
+        source,
+    })
+}
+
+fn outcome_line(outcome: SimulationOutcome) -> String {
+    match outcome {
+        SimulationOutcome::Completed => "every coder did his compiles".to_string(),
+        SimulationOutcome::BurnedOut { coder_id, elapsed } => {
+            format!("coder {coder_id} burned out at {:.1} ms", millis(elapsed))
+        }
+        SimulationOutcome::Interrupted { signal, elapsed } => {
+            format!("interrupted by {signal} at {:.1} ms", millis(elapsed))
+        }
+    }
+}
+
+/// A distribution as it's given on the command line
+fn distribution_label(distribution: Distribution) -> String {
+    match distribution {
+        Distribution::Constant(duration) => format!("{:.1} ms", millis(duration)),
+        Distribution::Uniform { min, max } => {
+            format!("uniform {:.1} to {:.1} ms", millis(min), millis(max))
+        }
+        Distribution::Normal { mean, std_dev } => format!(
+            "normal, mean {:.1} ms, std dev {:.1} ms",
+            millis(mean),
+            millis(std_dev)
+        ),
+        Distribution::Exponential { mean } => {
+            format!("exponential, mean {:.1} ms", millis(mean))
+        }
+        Distribution::LogNormal { mean, std_dev } => format!(
+            "lognormal, mean {:.1} ms, std dev {:.1} ms",
+            millis(mean),
+            millis(std_dev)
+        ),
+    }
+}
+
+fn scheduler_label(scheduler: SchedulerKind) -> String {
+    match scheduler {
+        SchedulerKind::Fifo => "fifo".to_string(),
+        SchedulerKind::Edf => "edf".to_string(),
+        SchedulerKind::Llf => "llf".to_string(),
+        SchedulerKind::Lottery { seed: None } => "lottery".to_string(),
+        SchedulerKind::Lottery { seed: Some(seed) } => format!("lottery:{seed}"),
+        SchedulerKind::RoundRobin => "round-robin".to_string(),
+        SchedulerKind::Aging => "aging".to_string(),
+    }
+}
+
+fn topology_label(topology: Topology) -> String {
+    match topology {
+        Topology::Ring => "ring".to_string(),
+        Topology::Star => "star".to_string(),
+        Topology::Complete => "complete".to_string(),
+        Topology::Grid { columns } => format!("grid:{columns}"),
+        Topology::Regular { degree, seed } => format!("random:{degree}:{seed}"),
+    }
+}
+
+fn backend_label(backend: Backend) -> String {
+    match backend {
+        Backend::Locks => "locks",
+        Backend::ChandyMisra => "chandy-misra",
+        Backend::Arbiter => "arbiter",
+    }
+    .to_string()
+}
+
+/// Text safe to put in SVG and HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Time between two ticks of the axis, 1, 2 or 5 times a power of ten
+/// milliseconds, for about ten ticks
+fn tick_step(end: Duration) -> f64 {
+    let rough = (millis(end) / 10.0).max(0.001);
+    let power = 10f64.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * power)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * power)
+}
+
+fn slice_color(kind: SliceKind) -> &'static str {
+    match kind {
+        SliceKind::Waiting => WAITING,
+        SliceKind::Compile => COMPILE,
+        SliceKind::Debug => DEBUG,
+        SliceKind::Refactor => REFACTOR,
+        SliceKind::Held { .. } => HELD,
+        SliceKind::Cooldown => COOLDOWN,
+    }
+}
+
+/// The run as a Gantt chart: a row per coder coloured by phase, a row per
+/// dongle with who held it, and a red line where a coder burned out
+pub fn gantt(timeline: &Timeline) -> String {
+    let coders = timeline.number_of_coders as f64;
+    let dongles = timeline.number_of_dongles as f64;
+    let dongles_top = TOP + coders * ROW_HEIGHT + SECTION_GAP;
+    let plot_bottom = dongles_top + dongles * ROW_HEIGHT;
+    let width = LABEL_WIDTH + PLOT_WIDTH + 20.0;
+    let height = plot_bottom + BOTTOM;
+
+    let end = millis(timeline.end).max(0.001);
+    let x = |at: Duration| LABEL_WIDTH + millis(at) / end * PLOT_WIDTH;
+    let row_top = |track: Track| match track {
+        Track::Coder(coder_id) => TOP + (coder_id - 1) as f64 * ROW_HEIGHT,
+        Track::Dongle(dongle_id) => dongles_top + (dongle_id - 1) as f64 * ROW_HEIGHT,
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width:.0}\" height=\"{height:.0}\" viewBox=\"0 0 {width:.0} {height:.0}\" font-family=\"sans-serif\" font-size=\"12\">"
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n<text x=\"{LABEL_WIDTH}\" y=\"18\" font-weight=\"bold\">codexion, {:.1} ms</text>",
+        millis(timeline.end)
+    );
+
+    // time axis, with a faint line across the rows at every tick
+    let step = tick_step(timeline.end);
+    // enough decimals for steps under a millisecond
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let ticks = (end / step + 1e-9).floor() as u32;
+    for idx in 0..=ticks {
+        let tick = idx as f64 * step;
+        let at = LABEL_WIDTH + tick / end * PLOT_WIDTH;
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{at:.1}\" y1=\"{TOP}\" x2=\"{at:.1}\" y2=\"{:.1}\" stroke=\"#eeeeee\"/>\n<text x=\"{at:.1}\" y=\"{:.1}\" text-anchor=\"middle\" fill=\"#555555\">{tick:.decimals$} ms</text>",
+            plot_bottom + 4.0,
+            plot_bottom + 18.0
+        );
+    }
+
+    for coder_id in 1..=timeline.number_of_coders {
+        let y = row_top(Track::Coder(coder_id)) + ROW_HEIGHT / 2.0 + 4.0;
+        let _ = writeln!(svg, "<text x=\"8\" y=\"{y:.1}\">coder {coder_id}</text>");
+    }
+    for dongle_id in 1..=timeline.number_of_dongles {
+        let y = row_top(Track::Dongle(dongle_id)) + ROW_HEIGHT / 2.0 + 4.0;
+        let _ = writeln!(
+            svg,
+            "<text x=\"8\" y=\"{y:.1}\" fill=\"#555555\">dongle {dongle_id}</text>"
+        );
+    }
+
+    for slice in &timeline.slices {
+        let left = x(slice.start);
+        let bar_width = (x(slice.end) - left).max(0.5);
+        let top = row_top(slice.track) + (ROW_HEIGHT - BAR_HEIGHT) / 2.0;
+        let owner = match slice.track {
+            Track::Coder(coder_id) => format!("coder {coder_id}"),
+            Track::Dongle(dongle_id) => format!("dongle {dongle_id}"),
+        };
+        let _ = write!(
+            svg,
+            "<rect x=\"{left:.2}\" y=\"{top:.1}\" width=\"{bar_width:.2}\" height=\"{BAR_HEIGHT}\" fill=\"{}\"><title>{owner}: {}, {:.1} to {:.1} ms</title></rect>",
+            slice_color(slice.kind),
+            slice.kind.name(),
+            millis(slice.start),
+            millis(slice.end)
+        );
+        // the holder's id, where it fits
+        if let SliceKind::Held { coder_id } = slice.kind
+            && bar_width >= 8.0 * (coder_id.to_string().len() as f64 + 1.0)
+        {
+            let _ = write!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" fill=\"white\" font-size=\"10\">{coder_id}</text>",
+                left + bar_width / 2.0,
+                top + BAR_HEIGHT / 2.0 + 3.5
+            );
+        }
+        svg.push('\n');
+    }
+
+    if let Some((coder_id, at)) = timeline.burnout {
+        let at_x = x(at);
+        let label_y = row_top(Track::Coder(coder_id)) + ROW_HEIGHT / 2.0 + 4.0;
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{at_x:.1}\" y1=\"{TOP}\" x2=\"{at_x:.1}\" y2=\"{plot_bottom:.1}\" stroke=\"{BURNOUT}\" stroke-width=\"2\"><title>coder {coder_id} burned out at {:.1} ms</title></line>\n<text x=\"{:.1}\" y=\"{label_y:.1}\" text-anchor=\"end\" fill=\"{BURNOUT}\" font-weight=\"bold\">coder {coder_id} burned out</text>",
+            millis(at),
+            at_x - 4.0
+        );
+    }
+
+    let legend = [
+        ("waiting", WAITING),
+        ("compile", COMPILE),
+        ("debug", DEBUG),
+        ("refactor", REFACTOR),
+        ("dongle held", HELD),
+        ("cooldown", COOLDOWN),
+        ("burnout", BURNOUT),
+    ];
+    let legend_y = plot_bottom + 36.0;
+    for (idx, (name, color)) in legend.iter().enumerate() {
+        let left = LABEL_WIDTH + idx as f64 * 110.0;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{left:.1}\" y=\"{legend_y:.1}\" width=\"12\" height=\"12\" fill=\"{color}\"/><text x=\"{:.1}\" y=\"{:.1}\">{name}</text>",
+            left + 16.0,
+            legend_y + 10.5
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// A page with nothing to fetch: what was run, the chart and the statistics tables
+pub fn html(args: &Args, outcome: SimulationOutcome, statistics: &Statistics, svg: &str) -> String {
+    let mut html = String::new();
+
+    html.push_str(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>codexion report</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; color: #222; }\n\
+         .run { display: flex; flex-wrap: wrap; gap: 2em; align-items: flex-start; }\n\
+         .chart { overflow-x: auto; max-width: 100%; }\n\
+         table { border-collapse: collapse; margin-bottom: 1.5em; }\n\
+         th, td { padding: 0.25em 0.75em; border-bottom: 1px solid #ddd; text-align: right; }\n\
+         th { background: #f4f4f4; }\n\
+         .summary td, .summary th { text-align: left; }\n\
+         .completed { color: #1a7f37; } .burned-out { color: #d62828; } .interrupted { color: #b26a00; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+
+    let (class, line) = match outcome {
+        SimulationOutcome::Completed => ("completed", outcome_line(outcome)),
+        SimulationOutcome::BurnedOut { .. } => ("burned-out", outcome_line(outcome)),
+        SimulationOutcome::Interrupted { .. } => ("interrupted", outcome_line(outcome)),
+    };
+    let _ = writeln!(
+        html,
+        "<h1>codexion report</h1>\n<p class=\"{class}\"><strong>{}</strong></p>",
+        escape(&line)
+    );
+
+    let summary = [
+        ("coders", args.number_of_coders.to_string()),
+        (
+            "time to burnout",
+            format!("{:.1} ms", millis(args.time_to_burnout)),
+        ),
+        ("time to compile", distribution_label(args.time_to_compile)),
+        ("time to debug", distribution_label(args.time_to_debug)),
+        (
+            "time to refactor",
+            distribution_label(args.time_to_refactor),
+        ),
+        (
+            "compiles required",
+            args.number_of_compiles_required.to_string(),
+        ),
+        ("cooldown", distribution_label(args.dongle_cooldown)),
+        ("scheduler", scheduler_label(args.scheduler)),
+        ("topology", topology_label(args.topology)),
+        ("backend", backend_label(args.backend)),
+        (
+            "clock",
+            match args.clock {
+                ClockMode::Real => "real",
+                ClockMode::Virtual => "virtual",
+            }
+            .to_string(),
+        ),
+        ("seed", args.seed.to_string()),
+        ("duration", format!("{:.1} ms", millis(statistics.duration))),
+        ("total compiles", statistics.total_compiles().to_string()),
+        ("Jain fairness index", format!("{:.3}", statistics.fairness)),
+    ];
+    html.push_str("<div class=\"run\">\n<table class=\"summary\">\n");
+    for (key, value) in summary {
+        let _ = writeln!(html, "<tr><th>{key}</th><td>{}</td></tr>", escape(&value));
+    }
+    html.push_str("</table>\n<div class=\"chart\">\n");
+    html.push_str(svg);
+    html.push_str("</div>\n</div>\n");
+
+    html.push_str(
+        "<h2>Coders</h2>\n<table>\n<tr><th>coder</th><th>compiles</th><th>longest gap ms</th><th>burnout %</th></tr>\n",
+    );
+    for coder in &statistics.coders {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.0}%</td></tr>",
+            coder.id,
+            coder.compiles,
+            millis(coder.longest_gap),
+            coder.longest_gap.as_secs_f64() / statistics.time_to_burnout.as_secs_f64() * 100.0
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(
+        "<h2>Dongles</h2>\n<table>\n<tr><th>dongle</th><th>waits</th><th>min ms</th><th>mean ms</th><th>p95 ms</th><th>max ms</th><th>used %</th><th>cooldown ms</th></tr>\n",
+    );
+    for dongle in &statistics.dongles {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.1}</td><td>{:.1}</td><td>{:.1}</td><td>{:.1}%</td><td>{:.1}</td></tr>",
+            dongle.id,
+            dongle.waits.count,
+            millis(dongle.waits.min),
+            millis(dongle.waits.mean),
+            millis(dongle.waits.p95),
+            millis(dongle.waits.max),
+            dongle.utilisation * 100.0,
+            millis(dongle.cooldown)
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    html
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportError::InvalidArgument(source) => write!(f, "{source}"),
+            ReportError::MissingValue(option) => {
+                write!(f, "Error: missing value for option '{option}'")
+            }
+            ReportError::Io { path, source } => {
+                write!(f, "Error: couldn't write the report to '{path}': {source}")
+            }
+        }
+    }
+}
+
+impl Error for ReportError {}
@@ -0,0 +1,58 @@
+/// Small seeded generator (SplitMix64), the same seed always gives the same numbers
+#[derive(Clone, Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// One of many independent generators from the same seed, told apart by `stream`
+    pub fn stream(seed: u64, stream: u64) -> Self {
+        let mut mixer = Self::new(seed ^ stream.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        Self::new(mixer.next_u64())
+    }
+
+    /// Where a coder draws his phases from
+    pub fn for_coder(seed: u64, coder_id: u32) -> Self {
+        Self::stream(seed, u64::from(coder_id))
+    }
+
+    /// Where a dongle draws its cooldowns from
+    pub fn for_dongle(seed: u64, dongle_id: u32) -> Self {
+        Self::stream(seed, u64::from(dongle_id) << 32)
+    }
+
+    /// Where a dongle draws the winners of its lottery from, apart from its cooldowns
+    pub fn for_lottery(seed: u64, dongle_id: u32) -> Self {
+        Self::for_dongle(!seed, dongle_id)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+
+    /// A number in `[0, 1)`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Fisher-Yates shuffle
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::{error::Error, fmt::Display, fs, io, path::Path};
+
+use crate::{
+    args::{Args, ArgsBuilder, ArgsError, ClockMode, CoderOverride, Field, LogFormat},
+    distribution::Distribution,
+    json::{JsonError, Value},
+    toml::{self, TomlError},
+};
+
+/// Why a scenario file couldn't be loaded
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    InvalidJson {
+        path: String,
+        source: JsonError,
+    },
+    InvalidToml {
+        path: String,
+        source: TomlError,
+    },
+    /// a key is missing, unknown or has the wrong type
+    InvalidKey {
+        path: String,
+        key: String,
+        expected: &'static str,
+    },
+    InvalidValue {
+        path: String,
+        source: Box<ArgsError>,
+    },
+}
+
+/// Apply the scenario file at `path` on top of `builder`.
+///
+/// The file is TOML, or JSON when it ends in `.json`. Top level keys are the
+/// flag names (`coders`, `burnout`, ...), with `[[coder]]` and `[[dongle]]`
+/// tables for per-id settings. A coder's `dongles` replace the ones the
+/// `topology` gives him:
+///
+/// ```toml
+/// name = "slow coder"
+/// coders = 5
+/// burnout = "800ms"
+/// topology = "star"
+///
+/// [[coder]]
+/// id = 3
+/// compile = "350ms"
+/// dongles = [1, 4, 5]
+///
+/// [[dongle]]
+/// id = 1
+/// cooldown = "50ms"
+/// ```
+pub fn load(path: &str, builder: ArgsBuilder) -> Result<ArgsBuilder, ScenarioError> {
+    let text = fs::read_to_string(path).map_err(|source| ScenarioError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+
+    let document = if Path::new(path).extension().is_some_and(|ext| ext == "json") {
+        Value::parse(&text).map_err(|source| ScenarioError::InvalidJson {
+            path: path.to_string(),
+            source,
+        })?
+    } else {
+        toml::parse(&text).map_err(|source| ScenarioError::InvalidToml {
+            path: path.to_string(),
+            source,
+        })?
+    };
+
+    Loader { path }.apply(&document, builder)
+}
+
+struct Loader<'a> {
+    path: &'a str,
+}
+
+impl Loader<'_> {
+    fn invalid_key(&self, key: &str, expected: &'static str) -> ScenarioError {
+        ScenarioError::InvalidKey {
+            path: self.path.to_string(),
+            key: key.to_string(),
+            expected,
+        }
+    }
+
+    fn invalid_value(&self, source: ArgsError) -> ScenarioError {
+        ScenarioError::InvalidValue {
+            path: self.path.to_string(),
+            source: Box::new(source),
+        }
+    }
+
+    fn apply(
+        &self,
+        document: &Value,
+        mut builder: ArgsBuilder,
+    ) -> Result<ArgsBuilder, ScenarioError> {
+        let entries = document
+            .as_object()
+            .ok_or_else(|| self.invalid_key("<root>", "a table"))?;
+
+        for (key, value) in entries {
+            builder = match key.as_str() {
+                "name" | "description" => builder,
+                "virtual" => match self.bool(key, value)? {
+                    true => builder.clock(ClockMode::Virtual),
+                    false => builder.clock(ClockMode::Real),
+                },
+                "log" => match value.as_str() {
+                    Some("text") => builder.log_format(LogFormat::Text),
+                    Some("json") => builder.log_format(LogFormat::Json),
+                    Some("quiet") => builder.log_format(LogFormat::Quiet),
+                    Some("tui") => builder.log_format(LogFormat::Tui),
+                    _ => {
+                        return Err(
+                            self.invalid_key(key, "\"text\", \"json\", \"quiet\" or \"tui\"")
+                        );
+                    }
+                },
+                "stats" => builder.stats(self.bool(key, value)?),
+                "watchdog" => builder.watchdog(self.bool(key, value)?),
+                "interactive" => builder.interactive(self.bool(key, value)?),
+                "coder" => {
+                    for table in self.tables(key, value)? {
+                        builder = self.coder(table, builder)?;
+                    }
+                    builder
+                }
+                "dongle" => {
+                    for table in self.tables(key, value)? {
+                        builder = self.dongle(table, builder)?;
+                    }
+                    builder
+                }
+                _ => {
+                    let field = Field::from_flag(key)
+                        .ok_or_else(|| self.invalid_key(key, "a known setting"))?;
+                    let value = self.scalar(key, value)?;
+                    field
+                        .apply(builder, &value, field.key())
+                        .map_err(|source| self.invalid_value(source))?
+                }
+            };
+        }
+
+        Ok(builder)
+    }
+
+    fn coder(&self, table: &Value, builder: ArgsBuilder) -> Result<ArgsBuilder, ScenarioError> {
+        let coder_id = self.id(table)?;
+        let mut coder_override = CoderOverride::default();
+        let mut builder = builder;
+
+        for (key, value) in table.as_object().unwrap_or_default() {
+            match key.as_str() {
+                "id" => (),
+                "compile" => {
+                    coder_override.time_to_compile = Some(self.distribution("compile", value)?)
+                }
+                "debug" => coder_override.time_to_debug = Some(self.distribution("debug", value)?),
+                "refactor" => {
+                    coder_override.time_to_refactor = Some(self.distribution("refactor", value)?)
+                }
+                "compiles" => {
+                    let value = self.scalar(key, value)?;
+                    coder_override.number_of_compiles_required = Some(
+                        Args::parse_count(&value, "compiles")
+                            .map_err(|source| self.invalid_value(source))?,
+                    )
+                }
+                "dongles" => {
+                    let dongle_ids = value
+                        .as_array()
+                        .and_then(|ids| {
+                            ids.iter()
+                                .map(|id| id.as_u64().and_then(|id| u32::try_from(id).ok()))
+                                .collect::<Option<Vec<_>>>()
+                        })
+                        .ok_or_else(|| {
+                            self.invalid_key("coder.dongles", "an array of dongle ids")
+                        })?;
+                    builder = builder.coder_dongles(coder_id, dongle_ids);
+                }
+                _ => return Err(self.invalid_key(&format!("coder.{key}"), "a known setting")),
+            }
+        }
+
+        Ok(builder.coder_override(coder_id, coder_override))
+    }
+
+    fn dongle(&self, table: &Value, builder: ArgsBuilder) -> Result<ArgsBuilder, ScenarioError> {
+        let dongle_id = self.id(table)?;
+        let mut cooldown = None;
+
+        for (key, value) in table.as_object().unwrap_or_default() {
+            match key.as_str() {
+                "id" => (),
+                "cooldown" => cooldown = Some(self.distribution("cooldown", value)?),
+                _ => return Err(self.invalid_key(&format!("dongle.{key}"), "a known setting")),
+            }
+        }
+
+        let cooldown = cooldown.ok_or_else(|| self.invalid_key("dongle.cooldown", "a duration"))?;
+        Ok(builder.dongle_cooldown_of(dongle_id, cooldown))
+    }
+
+    fn tables<'v>(&self, key: &str, value: &'v Value) -> Result<&'v [Value], ScenarioError> {
+        let tables = value
+            .as_array()
+            .ok_or_else(|| self.invalid_key(key, "an array of tables"))?;
+        if tables.iter().any(|table| table.as_object().is_none()) {
+            return Err(self.invalid_key(key, "an array of tables"));
+        }
+        Ok(tables)
+    }
+
+    fn id(&self, table: &Value) -> Result<u32, ScenarioError> {
+        table
+            .get("id")
+            .and_then(Value::as_u64)
+            .and_then(|id| u32::try_from(id).ok())
+            .ok_or_else(|| self.invalid_key("id", "a positive integer"))
+    }
+
+    fn bool(&self, key: &str, value: &Value) -> Result<bool, ScenarioError> {
+        match value {
+            Value::Bool(value) => Ok(*value),
+            _ => Err(self.invalid_key(key, "true or false")),
+        }
+    }
+
+    /// Durations are millis when given as numbers, or strings with a unit,
+    /// distributions are strings as on the command line
+    fn distribution(
+        &self,
+        key: &'static str,
+        value: &Value,
+    ) -> Result<Distribution, ScenarioError> {
+        let value = self.scalar(key, value)?;
+        Distribution::parse(&value, key).map_err(|source| self.invalid_value(source))
+    }
+
+    /// Numbers and strings as the command line would have them
+    fn scalar(&self, key: &str, value: &Value) -> Result<String, ScenarioError> {
+        match value {
+            Value::String(string) => Ok(string.clone()),
+            Value::Number(number) => Ok(number.to_string()),
+            _ => Err(self.invalid_key(key, "a number or a string")),
+        }
+    }
+}
+
+impl Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Io { path, source } => {
+                write!(f, "Error: can't read scenario '{path}': {source}")
+            }
+            ScenarioError::InvalidJson { path, source } => {
+                write!(f, "Error: invalid JSON in scenario '{path}': {source}")
+            }
+            ScenarioError::InvalidToml { path, source } => write!(
+                f,
+                "Error: invalid TOML in scenario '{path}' on line {}: {}",
+                source.line, source.message
+            ),
+            ScenarioError::InvalidKey {
+                path,
+                key,
+                expected,
+            } => write!(
+                f,
+                "Error: key '{key}' in scenario '{path}' should be {expected}"
+            ),
+            ScenarioError::InvalidValue { path, source } => {
+                write!(f, "{source} (in scenario '{path}')")
+            }
+        }
+    }
+}
+
+impl Error for ScenarioError {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn apply(text: &str) -> Result<Args, ScenarioError> {
+        let document = toml::parse(text).expect("the TOML should be valid");
+        let builder = Loader {
+            path: "scenario.toml",
+        }
+        .apply(&document, Args::builder())?;
+        Ok(builder.build().expect("the arguments should be valid"))
+    }
+
+    fn invalid_key(text: &str) -> (String, &'static str) {
+        match apply(text) {
+            Err(ScenarioError::InvalidKey { key, expected, .. }) => (key, expected),
+            other => panic!("expected an invalid key, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn settings_coders_and_dongles() {
+        let args = apply(
+            "name = \"slow coder\"\n\
+             coders = 5\n\
+             burnout = \"800ms\"\n\
+             virtual = true\n\
+             log = \"quiet\"\n\
+             [[coder]]\n\
+             id = 3\n\
+             compile = 350\n\
+             compiles = 2\n\
+             dongles = [1, 4, 5]\n\
+             [[dongle]]\n\
+             id = 1\n\
+             cooldown = \"50ms\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(args.number_of_coders, 5);
+        assert_eq!(args.time_to_burnout, Duration::from_millis(800));
+        assert_eq!(args.clock, ClockMode::Virtual);
+        assert_eq!(args.log_format, LogFormat::Quiet);
+        let coder = &args.coder_overrides[&3];
+        assert_eq!(
+            coder.time_to_compile,
+            Some(Distribution::Constant(Duration::from_millis(350)))
+        );
+        assert_eq!(coder.number_of_compiles_required, Some(2));
+        assert_eq!(args.coder_dongles[&3], vec![1, 4, 5]);
+        assert_eq!(
+            args.dongle_cooldowns[&1],
+            Distribution::Constant(Duration::from_millis(50))
+        );
+    }
+
+    #[test]
+    fn ids_must_fit() {
+        let too_big = u64::from(u32::MAX) + 2;
+        assert_eq!(
+            invalid_key(&format!("[[coder]]\nid = {too_big}")),
+            ("id".to_string(), "a positive integer")
+        );
+        assert_eq!(
+            invalid_key(&format!("[[coder]]\nid = 1\ndongles = [1, {too_big}]")),
+            ("coder.dongles".to_string(), "an array of dongle ids")
+        );
+        assert_eq!(
+            invalid_key("[[dongle]]\nid = -1\ncooldown = 5"),
+            ("id".to_string(), "a positive integer")
+        );
+    }
+
+    #[test]
+    fn malformed_settings() {
+        assert_eq!(
+            invalid_key("coderz = 5"),
+            ("coderz".to_string(), "a known setting")
+        );
+        assert_eq!(
+            invalid_key("virtual = 1"),
+            ("virtual".to_string(), "true or false")
+        );
+        assert_eq!(
+            invalid_key("[coder]\nid = 1"),
+            ("coder".to_string(), "an array of tables")
+        );
+        assert_eq!(
+            invalid_key("[[coder]]\nid = 1\nspeed = 2"),
+            ("coder.speed".to_string(), "a known setting")
+        );
+        assert_eq!(
+            invalid_key("[[dongle]]\nid = 1"),
+            ("dongle.cooldown".to_string(), "a duration")
+        );
+        assert_eq!(
+            invalid_key("[[dongle]]\nid = 1\ncooldown = 5\ncooldwn_ms = 5"),
+            ("dongle.cooldwn_ms".to_string(), "a known setting")
+        );
+        assert!(matches!(
+            apply("coders = \"many\""),
+            Err(ScenarioError::InvalidValue { .. })
+        ));
+    }
+}
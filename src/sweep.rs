@@ -0,0 +1,387 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    num::{NonZeroUsize, ParseIntError},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    args::{Args, ArgsError, ClockMode, Field, LogFormat},
+    clock::millis,
+    codexion::{Codexion, SimulationOutcome, signals},
+};
+
+/// Settings to run in every combination, and how
+#[derive(Debug)]
+pub struct SweepOptions {
+    /// per swept argument, its scenario key and every value it takes
+    pub axes: Vec<(&'static str, Vec<String>)>,
+    /// switches and `--scenario`, given to every run as they are
+    pub base: Vec<String>,
+    /// runs of each combination, the run `i` uses `seed + i - 1`
+    pub runs: u32,
+    /// runs at the same time, the real clock always runs one at a time
+    pub jobs: usize,
+    /// where to write the CSV, stdout when not given
+    pub out: Option<String>,
+}
+
+/// How one run of one combination ended
+#[derive(Clone, Debug)]
+pub struct SweepRow {
+    /// value of each axis, in the order of the axes
+    pub values: Vec<String>,
+    /// from 1
+    pub run: u32,
+    pub seed: u64,
+    pub outcome: SimulationOutcome,
+    pub compiles: u32,
+    pub compiles_required: u32,
+    pub fairness: f64,
+    pub duration: Duration,
+}
+
+#[derive(Debug)]
+pub enum SweepError {
+    MissingValue(String),
+    InvalidNumber {
+        option: String,
+        source: ParseIntError,
+    },
+    InvalidRange {
+        option: String,
+        value: String,
+    },
+    UnknownOption(String),
+    /// a combination the codexion can't run, with the values that make it
+    InvalidCombination {
+        values: String,
+        source: ArgsError,
+    },
+    Io(io::Error),
+}
+
+impl TryFrom<&[String]> for SweepOptions {
+    type Error = SweepError;
+
+    /// Any argument that takes a value may be a list (`fifo,edf`), a range
+    /// (`2..50`) or both, other arguments are given to every run
+    fn try_from(args: &[String]) -> Result<Self, Self::Error> {
+        let mut options = Self {
+            axes: Vec::new(),
+            base: Vec::new(),
+            runs: 1,
+            jobs: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            out: None,
+        };
+        let mut args = args.iter();
+
+        while let Some(token) = args.next() {
+            let Some(flag) = token.strip_prefix("--") else {
+                return Err(SweepError::UnknownOption(token.clone()));
+            };
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let field = Field::from_flag(name);
+            let takes_value =
+                matches!(name, "runs" | "jobs" | "out" | "scenario") || field.is_some();
+            if !takes_value {
+                options.base.push(token.clone());
+                continue;
+            }
+            let value = match inline_value {
+                Some(value) => value,
+                None => args
+                    .next()
+                    .ok_or_else(|| SweepError::MissingValue(token.clone()))?
+                    .clone(),
+            };
+            let count = |option: &str| -> Result<u32, SweepError> {
+                value.parse().map_err(|source| SweepError::InvalidNumber {
+                    option: option.to_string(),
+                    source,
+                })
+            };
+
+            match (name, field) {
+                ("runs", _) => options.runs = count("--runs")?.max(1),
+                ("jobs", _) => options.jobs = count("--jobs")?.max(1) as usize,
+                ("out", _) => options.out = Some(value),
+                (_, Some(field)) => {
+                    let values = expand(&value).ok_or_else(|| SweepError::InvalidRange {
+                        option: format!("--{name}"),
+                        value: value.clone(),
+                    })?;
+                    // a later value of the same argument replaces the earlier one
+                    options.axes.retain(|&(key, _)| key != field.key());
+                    options.axes.push((field.key(), values));
+                }
+                (_, None) => options.base.extend(["--scenario".to_string(), value]),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Every value of a list of values and ranges, `None` if a range is invalid
+fn expand(value: &str) -> Option<Vec<String>> {
+    let mut values = Vec::new();
+
+    for item in value.split(',') {
+        let Some((from, to)) = item.split_once("..") else {
+            values.push(item.to_string());
+            continue;
+        };
+        let (to, step) = match to.split_once(':') {
+            Some((to, step)) => (to, Some(step)),
+            None => (to, None),
+        };
+
+        // plain numbers stay plain, they're counts or millis
+        if let (Ok(from), Ok(to), Ok(step)) = (
+            from.parse::<u64>(),
+            to.parse::<u64>(),
+            step.map_or(Ok(1), str::parse::<u64>),
+        ) {
+            if from > to || step == 0 {
+                return None;
+            }
+            values.extend((from..=to).step_by(step as usize).map(|n| n.to_string()));
+            continue;
+        }
+
+        let duration = |value| Args::parse_duration(value, "--sweep").ok();
+        let from = duration(from)?;
+        let to = duration(to)?;
+        let step = step.map_or(Some(Duration::from_millis(1)), duration)?;
+        if from > to || step.is_zero() {
+            return None;
+        }
+        let mut current = from;
+        while current <= to {
+            values.push(duration_value(current));
+            current += step;
+        }
+    }
+    Some(values)
+}
+
+/// A duration as the command line takes it, in millis when it's whole
+fn duration_value(duration: Duration) -> String {
+    let micros = duration.as_micros();
+    if micros.is_multiple_of(1000) {
+        format!("{}ms", micros / 1000)
+    } else {
+        format!("{micros}us")
+    }
+}
+
+impl SweepOptions {
+    /// Every combination of the axes' values, the last axis changing first
+    pub fn combinations(&self) -> Vec<Vec<String>> {
+        let mut combinations = vec![Vec::new()];
+
+        for (_, values) in &self.axes {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination: Vec<String>| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push(value.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+
+    /// Arguments of one combination, quiet and without anything printed at the end
+    fn args(&self, values: &[String]) -> Result<Args, SweepError> {
+        let mut tokens = vec!["sweep".to_string()];
+        tokens.extend(self.base.iter().cloned());
+        for ((key, _), value) in self.axes.iter().zip(values) {
+            tokens.extend([format!("--{key}"), value.clone()]);
+        }
+
+        let mut args = Args::try_from(tokens).map_err(|source| SweepError::InvalidCombination {
+            values: self.describe(values),
+            source,
+        })?;
+        args.log_format = LogFormat::Quiet;
+        args.stats = false;
+        args.watchdog = false;
+        args.interactive = false;
+        args.interruption_summary = false;
+        args.post_mortem = None;
+        args.trace_out = None;
+        args.record = None;
+        Ok(args)
+    }
+
+    fn describe(&self, values: &[String]) -> String {
+        self.axes
+            .iter()
+            .zip(values)
+            .map(|((key, _), value)| format!("--{key} {value}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Run one combination, with its `run`th seed
+fn run_once(args: &Args, values: &[String], run: u32) -> SweepRow {
+    let mut args = args.clone();
+    args.seed = args.seed.wrapping_add(run as u64 - 1);
+    let compiles_required = (1..=args.number_of_coders)
+        .map(|coder_id| args.coder_profile(coder_id).number_of_compiles_required)
+        .sum();
+    let seed = args.seed;
+
+    let codexion = Codexion::new(args);
+    let outcome = codexion.start();
+    let statistics = codexion.statistics();
+
+    SweepRow {
+        values: values.to_vec(),
+        run,
+        seed,
+        outcome,
+        compiles: statistics.total_compiles(),
+        compiles_required,
+        fairness: statistics.fairness,
+        duration: statistics.duration,
+    }
+}
+
+/// Run every combination and write one CSV line per run
+pub fn run(args: &[String]) -> Result<(), SweepError> {
+    let options = SweepOptions::try_from(args)?;
+    // a signal from here on stops every run of the sweep
+    signals::clear_stop();
+
+    // check every combination before running any
+    let mut runs = Vec::new();
+    for values in options.combinations() {
+        let args = options.args(&values)?;
+        for run in 1..=options.runs {
+            runs.push((args.clone(), values.clone(), run));
+        }
+    }
+
+    // real clock runs would slow each other down and change what they measure
+    let jobs = match runs.first() {
+        Some((args, _, _)) if args.clock == ClockMode::Virtual => options.jobs,
+        _ => 1,
+    };
+    let rows: Vec<Mutex<Option<SweepRow>>> = runs.iter().map(|_| Mutex::new(None)).collect();
+    let next_run = AtomicUsize::new(0);
+    // a signal stops the sweep, not only the run it caught
+    let interrupted = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(runs.len()) {
+            scope.spawn(|| {
+                while !interrupted.load(Ordering::Relaxed) {
+                    let idx = next_run.fetch_add(1, Ordering::Relaxed);
+                    let Some((args, values, run)) = runs.get(idx) else {
+                        break;
+                    };
+                    let row = run_once(args, values, *run);
+                    if let SimulationOutcome::Interrupted { .. } = row.outcome {
+                        interrupted.store(true, Ordering::Relaxed);
+                    }
+                    *rows[idx].lock().unwrap() = Some(row);
+                }
+            });
+        }
+    });
+
+    let mut csv = header(&options);
+    for row in rows {
+        if let Some(row) = row.into_inner().unwrap() {
+            csv.push_str(&csv_line(&row));
+        }
+    }
+
+    match &options.out {
+        Some(path) => fs::write(path, csv),
+        None => io::stdout().write_all(csv.as_bytes()),
+    }
+    .map_err(SweepError::Io)
+}
+
+fn header(options: &SweepOptions) -> String {
+    let mut columns: Vec<&str> = options.axes.iter().map(|&(key, _)| key).collect();
+    columns.extend([
+        "run",
+        "run_seed",
+        "outcome",
+        "burned_out_coder",
+        "first_burnout_ms",
+        "compiles_completed",
+        "compiles_required",
+        "fairness",
+        "duration_ms",
+    ]);
+    format!("{}\n", columns.join(","))
+}
+
+fn csv_line(row: &SweepRow) -> String {
+    let (outcome, coder_id, burnout) = match row.outcome {
+        SimulationOutcome::Completed => ("completed", String::new(), String::new()),
+        SimulationOutcome::BurnedOut { coder_id, elapsed } => (
+            "burned_out",
+            coder_id.to_string(),
+            format!("{:.3}", millis(elapsed)),
+        ),
+        SimulationOutcome::Interrupted { .. } => ("interrupted", String::new(), String::new()),
+    };
+
+    let mut fields = row.values.clone();
+    fields.extend([
+        row.run.to_string(),
+        row.seed.to_string(),
+        outcome.to_string(),
+        coder_id,
+        burnout,
+        row.compiles.to_string(),
+        row.compiles_required.to_string(),
+        format!("{:.4}", row.fairness),
+        format!("{:.3}", millis(row.duration)),
+    ]);
+    format!("{}\n", fields.join(","))
+}
+
+impl Display for SweepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SweepError::MissingValue(option) => {
+                write!(f, "Error: missing value for option '{option}'")
+            }
+            SweepError::InvalidNumber { option, source } => {
+                write!(f, "Error: invalid number for option '{option}': {source}")
+            }
+            SweepError::InvalidRange { option, value } => write!(
+                f,
+                "Error: invalid range '{value}' for option '{option}' (expected FROM..TO[:STEP], from the smallest)"
+            ),
+            SweepError::UnknownOption(option) => write!(f, "Error: unknown option '{option}'"),
+            SweepError::InvalidCombination { values, source } => {
+                write!(f, "{source} (with {values})")
+            }
+            SweepError::Io(source) => write!(f, "Error: can't write the CSV: {source}"),
+        }
+    }
+}
+
+impl Error for SweepError {}
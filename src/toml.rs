@@ -0,0 +1,209 @@
+use crate::json::Value;
+
+/// Error in a TOML document, with the 1-based line it's on
+#[derive(Debug)]
+pub struct TomlError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+/// Parse the part of TOML scenario files use into the same tree as JSON.
+///
+/// Supported: `key = value` pairs, `[table]` and `[[array of tables]]`
+/// headers one level deep, `#` comments, and values that are strings,
+/// numbers, booleans or single-line arrays of those.
+pub fn parse(text: &str) -> Result<Value, TomlError> {
+    let mut root: Vec<(String, Value)> = Vec::new();
+    // name of the table the next keys go to, `None` for the root
+    let mut current: Option<String> = None;
+
+    for (idx, raw_line) in text.lines().enumerate() {
+        let line_number = idx + 1;
+        let error = |message| TomlError {
+            line: line_number,
+            message,
+        };
+        let line = strip_comment(raw_line).trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("[[") {
+            let name = header.strip_suffix("]]").ok_or(error("unclosed header"))?;
+            let name = name.trim().to_string();
+            match entry(&mut root, &name) {
+                Some(Value::Array(tables)) => tables.push(Value::Object(Vec::new())),
+                Some(_) => return Err(error("key is already something else than a table array")),
+                None => root.push((name.clone(), Value::Array(vec![Value::Object(Vec::new())]))),
+            }
+            current = Some(name);
+        } else if let Some(header) = line.strip_prefix('[') {
+            let name = header.strip_suffix(']').ok_or(error("unclosed header"))?;
+            let name = name.trim().to_string();
+            if entry(&mut root, &name).is_some() {
+                return Err(error("table defined twice"));
+            }
+            root.push((name.clone(), Value::Object(Vec::new())));
+            current = Some(name);
+        } else {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(error("expected 'key = value'"))?;
+            let key = unquote_key(key.trim()).ok_or(error("invalid key"))?;
+            let value = parse_value(value.trim()).ok_or(error("invalid value"))?;
+
+            let table = match &current {
+                None => &mut root,
+                Some(name) => match entry(&mut root, name) {
+                    Some(Value::Object(entries)) => entries,
+                    Some(Value::Array(tables)) => match tables.last_mut() {
+                        Some(Value::Object(entries)) => entries,
+                        _ => return Err(error("invalid table")),
+                    },
+                    _ => return Err(error("invalid table")),
+                },
+            };
+            if table.iter().any(|(existing, _)| *existing == key) {
+                return Err(error("key defined twice"));
+            }
+            table.push((key, value));
+        }
+    }
+
+    Ok(Value::Object(root))
+}
+
+fn entry<'a>(table: &'a mut [(String, Value)], key: &str) -> Option<&'a mut Value> {
+    table
+        .iter_mut()
+        .find(|(existing, _)| existing == key)
+        .map(|(_, value)| value)
+}
+
+/// Cut the line at a `#` that isn't inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (idx, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), c) if c == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..idx],
+            _ => (),
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn unquote_key(key: &str) -> Option<String> {
+    if key.starts_with('"') {
+        return Value::parse(key).ok()?.as_str().map(str::to_string);
+    }
+    let bare = key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    (bare && !key.is_empty()).then(|| key.to_string())
+}
+
+/// TOML values of the supported kinds are written like JSON, apart from
+/// literal strings and underscores in numbers
+fn parse_value(value: &str) -> Option<Value> {
+    if let Some(literal) = value.strip_prefix('\'') {
+        return Some(Value::String(literal.strip_suffix('\'')?.to_string()));
+    }
+    if value.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') {
+        return Value::parse(&value.trim_start_matches('+').replace('_', "")).ok();
+    }
+    Value::parse(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> (usize, &'static str) {
+        let error = parse(text).expect_err("the TOML should be invalid");
+        (error.line, error.message)
+    }
+
+    #[test]
+    fn tables_and_arrays_of_tables() {
+        let document = parse(
+            "coders = 3 # three of them\n\
+             \n\
+             [[coder]]\n\
+             id = 1\n\
+             dongles = [1, 2]\n\
+             [[coder]]\n\
+             id = 2\n\
+             [settings]\n\
+             \"quoted key\" = true\n",
+        )
+        .unwrap();
+
+        assert_eq!(document.get("coders"), Some(&Value::Number(3.0)));
+        let coders = document.get("coder").and_then(Value::as_array).unwrap();
+        assert_eq!(coders.len(), 2);
+        assert_eq!(coders[0].get("id").and_then(Value::as_u64), Some(1));
+        assert_eq!(
+            coders[0].get("dongles"),
+            Some(&Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]))
+        );
+        assert_eq!(coders[1].get("id").and_then(Value::as_u64), Some(2));
+        assert_eq!(coders[1].get("dongles"), None);
+        assert_eq!(
+            document
+                .get("settings")
+                .and_then(|table| table.get("quoted key")),
+            Some(&Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn strings_and_numbers() {
+        let document = parse(
+            "basic = \"a \\\"#\\\" b\\n\" # comment\n\
+             literal = 'C:\\path # not a comment'\n\
+             big = 1_000_000\n\
+             positive = +5\n\
+             negative = -2.5\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            document.get("basic").and_then(Value::as_str),
+            Some("a \"#\" b\n")
+        );
+        assert_eq!(
+            document.get("literal").and_then(Value::as_str),
+            Some("C:\\path # not a comment")
+        );
+        assert_eq!(document.get("big").and_then(Value::as_u64), Some(1_000_000));
+        assert_eq!(document.get("positive").and_then(Value::as_u64), Some(5));
+        assert_eq!(document.get("negative"), Some(&Value::Number(-2.5)));
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(error("[coder"), (1, "unclosed header"));
+        assert_eq!(error("a = 1\n[[coder]"), (2, "unclosed header"));
+        assert_eq!(error("[a]\n[a]"), (2, "table defined twice"));
+        assert_eq!(
+            error("coder = 1\n[[coder]]"),
+            (2, "key is already something else than a table array")
+        );
+        assert_eq!(error("a = 1\na = 2"), (2, "key defined twice"));
+        assert_eq!(error("just words"), (1, "expected 'key = value'"));
+        assert_eq!(error("bad key = 1"), (1, "invalid key"));
+        assert_eq!(error("a = 'unclosed"), (1, "invalid value"));
+        assert_eq!(error("a = [1, 2"), (1, "invalid value"));
+        assert_eq!(error("a = 1_x"), (1, "invalid value"));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{args::ArgsError, rng::Rng};
+
+/// How coders are connected to the dongles they need
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Topology {
+    /// coder `i` between dongles `i` and `i + 1`, the classic table
+    Ring,
+    /// every coder needs the shared dongle 1 and a dongle of his own
+    Star,
+    /// every coder needs every dongle
+    Complete,
+    /// coders on a grid `columns` wide, sharing one dongle with each neighbour
+    Grid { columns: u32 },
+    /// every coder needs `degree` dongles and every dongle serves `degree`
+    /// coders, picked at random from `seed`
+    Regular { degree: u32, seed: u64 },
+}
+
+/// Dongles each coder needs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Graph {
+    pub number_of_dongles: u32,
+    /// per coder, dongle indices sorted in the global acquiring order
+    pub coders: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    /// Connect `number_of_coders` coders
+    pub fn graph(self, number_of_coders: u32) -> Graph {
+        let n = number_of_coders as usize;
+
+        let (number_of_dongles, coders) = match self {
+            Topology::Ring => (n, (0..n).map(|i| vec![i, (i + 1) % n]).collect()),
+            Topology::Star => (n + 1, (0..n).map(|i| vec![0, i + 1]).collect()),
+            Topology::Complete => (n, (0..n).map(|_| (0..n).collect()).collect()),
+            Topology::Grid { columns } => Self::grid(n, columns.max(1) as usize),
+            Topology::Regular { degree, seed } => Self::regular(n, degree as usize, seed),
+        };
+
+        let mut graph = Graph {
+            number_of_dongles: number_of_dongles as u32,
+            coders,
+        };
+        graph.sort();
+        graph
+    }
+
+    /// One dongle on every edge of the grid, numbered row by row
+    fn grid(n: usize, columns: usize) -> (usize, Vec<Vec<usize>>) {
+        let mut coders = vec![Vec::new(); n];
+        let mut number_of_dongles = 0;
+
+        for i in 0..n {
+            let right = i + 1;
+            let below = i + columns;
+            if right % columns != 0 && right < n {
+                coders[i].push(number_of_dongles);
+                coders[right].push(number_of_dongles);
+                number_of_dongles += 1;
+            }
+            if below < n {
+                coders[i].push(number_of_dongles);
+                coders[below].push(number_of_dongles);
+                number_of_dongles += 1;
+            }
+        }
+        (number_of_dongles, coders)
+    }
+
+    /// Coder `i` needs the dongles at `degree` distinct random offsets from
+    /// `i`, behind a random renumbering, so every dongle is needed `degree` times
+    fn regular(n: usize, degree: usize, seed: u64) -> (usize, Vec<Vec<usize>>) {
+        let mut rng = Rng::new(seed);
+        let mut offsets: Vec<usize> = (0..n).collect();
+        rng.shuffle(&mut offsets);
+        let mut dongles: Vec<usize> = (0..n).collect();
+        rng.shuffle(&mut dongles);
+
+        let coders = (0..n)
+            .map(|i| {
+                offsets[..degree.min(n)]
+                    .iter()
+                    .map(|offset| dongles[(i + offset) % n])
+                    .collect()
+            })
+            .collect();
+        (n, coders)
+    }
+
+    /// Check the generator can connect `number_of_coders` coders
+    pub fn validate(self, number_of_coders: u32) -> Result<(), ArgsError> {
+        match self {
+            Topology::Grid { columns: 0 } => Err(ArgsError::InvalidNumberRange {
+                argument: "grid columns",
+                min_value: 1,
+            }),
+            Topology::Regular { degree, .. } if degree < 1 || degree > number_of_coders => {
+                Err(ArgsError::InvalidDegree {
+                    degree,
+                    number_of_coders,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Graph {
+    /// Put every coder's dongles in the global acquiring order
+    pub fn sort(&mut self) {
+        for dongles in &mut self.coders {
+            dongles.sort_unstable();
+        }
+    }
+}
+
+impl TryFrom<&str> for Topology {
+    type Error = ArgsError;
+
+    /// `ring`, `star`, `complete`, `grid:COLUMNS` or `random:DEGREE[:SEED]`
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || ArgsError::InvalidTopology(value.to_string());
+        let mut parts = value.split(':');
+        let name = parts.next().unwrap_or_default();
+        let numbers = parts
+            .map(|part| part.parse::<u64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let small = |number: u64| u32::try_from(number).map_err(|_| invalid());
+
+        let topology = match (name, numbers.as_slice()) {
+            ("ring", []) => Topology::Ring,
+            ("star", []) => Topology::Star,
+            ("complete", []) => Topology::Complete,
+            ("grid", &[columns]) => Topology::Grid {
+                columns: small(columns)?,
+            },
+            ("random", &[degree]) => Topology::Regular {
+                degree: small(degree)?,
+                seed: 0,
+            },
+            ("random", &[degree, seed]) => Topology::Regular {
+                degree: small(degree)?,
+                seed,
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(topology)
+    }
+}
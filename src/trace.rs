@@ -0,0 +1,329 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use crate::{
+    args::{Args, CoderProfile},
+    distribution::Distribution,
+    logging::{Event, EventKind, Observer},
+};
+
+/// Process of the coder tracks
+const CODERS_PID: u32 = 1;
+/// Process of the dongle tracks
+const DONGLES_PID: u32 = 2;
+
+/// Records every event of a run, to write it as a trace once it's over
+pub struct Trace {
+    profiles: Vec<CoderProfile>,
+    /// per dongle id, from 1
+    cooldowns: Vec<Distribution>,
+    events: Mutex<Vec<Event>>,
+}
+
+/// What every coder and dongle went through during a run, as periods of time
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    /// time since the start of the run when it stopped
+    pub end: Duration,
+    pub number_of_coders: u32,
+    pub number_of_dongles: u32,
+    pub slices: Vec<Slice>,
+    pub handovers: Vec<Handover>,
+    /// who burned out, and when
+    pub burnout: Option<(u32, Duration)>,
+}
+
+/// A period a coder or a dongle spent doing one thing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slice {
+    pub track: Track,
+    pub kind: SliceKind,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Track {
+    Coder(u32),
+    Dongle(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SliceKind {
+    /// for his dongles
+    Waiting,
+    Compile,
+    Debug,
+    Refactor,
+    Held {
+        coder_id: u32,
+    },
+    Cooldown,
+}
+
+/// A dongle released by a coder and taken by another who was waiting for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handover {
+    pub dongle_id: u32,
+    pub from_coder: u32,
+    pub to_coder: u32,
+    pub released_at: Duration,
+    pub acquired_at: Duration,
+}
+
+/// A dongle's last release, where a handover starts
+struct Release {
+    coder_id: u32,
+    at: Duration,
+}
+
+impl SliceKind {
+    pub fn name(&self) -> String {
+        match self {
+            SliceKind::Waiting => "waiting".to_string(),
+            SliceKind::Compile => "compile".to_string(),
+            SliceKind::Debug => "debug".to_string(),
+            SliceKind::Refactor => "refactor".to_string(),
+            SliceKind::Held { coder_id } => format!("held by coder {coder_id}"),
+            SliceKind::Cooldown => "cooldown".to_string(),
+        }
+    }
+}
+
+impl Trace {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            profiles: (1..=args.number_of_coders)
+                .map(|coder_id| args.coder_profile(coder_id))
+                .collect(),
+            cooldowns: (1..=args.graph().number_of_dongles)
+                .map(|dongle_id| args.cooldown_of(dongle_id))
+                .collect(),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The run up to `end` as periods: phases and waits of the coders,
+    /// holds and cooldowns of the dongles
+    pub fn timeline(&self, end: Duration) -> Timeline {
+        let events = self.events.lock().unwrap();
+        let mut timeline = Timeline {
+            end,
+            number_of_coders: self.profiles.len() as u32,
+            number_of_dongles: self.cooldowns.len() as u32,
+            ..Timeline::default()
+        };
+
+        // every coder waits for his dongles from the start
+        let mut waiting_since: BTreeMap<u32, Duration> = (1..=timeline.number_of_coders)
+            .map(|coder_id| (coder_id, Duration::ZERO))
+            .collect();
+        let mut held_since: BTreeMap<u32, (u32, Duration)> = BTreeMap::new();
+        let mut last_release: BTreeMap<u32, Release> = BTreeMap::new();
+        let mut compiles: BTreeMap<u32, u32> = BTreeMap::new();
+
+        for event in events.iter() {
+            let at = event.elapsed;
+            let coder_id = event.coder_id;
+            let mut slices = |track, kind, start: Duration, until: Duration| {
+                timeline.slices.push(Slice {
+                    track,
+                    kind,
+                    start,
+                    end: until.min(end),
+                });
+            };
+
+            match event.kind {
+                EventKind::Acquire { dongle_id } => {
+                    held_since.insert(dongle_id, (coder_id, at));
+                    let waited = waiting_since
+                        .get(&coder_id)
+                        .zip(last_release.get(&dongle_id));
+                    if let Some((&since, release)) = waited
+                        && release.coder_id != coder_id
+                        && since <= release.at
+                    {
+                        timeline.handovers.push(Handover {
+                            dongle_id,
+                            from_coder: release.coder_id,
+                            to_coder: coder_id,
+                            released_at: release.at,
+                            acquired_at: at,
+                        });
+                    }
+                }
+                EventKind::Compile => {
+                    if let Some(since) = waiting_since.remove(&coder_id)
+                        && since < at
+                    {
+                        slices(Track::Coder(coder_id), SliceKind::Waiting, since, at);
+                    }
+                    let length = self.length(event, |profile| profile.time_to_compile);
+                    slices(Track::Coder(coder_id), SliceKind::Compile, at, at + length);
+                    *compiles.entry(coder_id).or_default() += 1;
+                }
+                EventKind::Release { dongle_id } => {
+                    if let Some((holder, since)) = held_since.remove(&dongle_id) {
+                        let held = SliceKind::Held { coder_id: holder };
+                        slices(Track::Dongle(dongle_id), held, since, at);
+                    }
+                    let cooldown = event.duration.unwrap_or_else(|| {
+                        self.cooldowns
+                            .get(dongle_id as usize - 1)
+                            .map_or(Duration::ZERO, Distribution::mean)
+                    });
+                    if !cooldown.is_zero() {
+                        let track = Track::Dongle(dongle_id);
+                        slices(track, SliceKind::Cooldown, at, at + cooldown);
+                    }
+                    last_release.insert(dongle_id, Release { coder_id, at });
+                }
+                EventKind::Debug => {
+                    let length = self.length(event, |profile| profile.time_to_debug);
+                    slices(Track::Coder(coder_id), SliceKind::Debug, at, at + length);
+                }
+                EventKind::Refactor => {
+                    let length = self.length(event, |profile| profile.time_to_refactor);
+                    slices(Track::Coder(coder_id), SliceKind::Refactor, at, at + length);
+                    // unless he's done, he waits for his dongles again
+                    let required = self
+                        .profiles
+                        .get(coder_id as usize - 1)
+                        .map_or(0, |profile| profile.number_of_compiles_required);
+                    if compiles.get(&coder_id).copied().unwrap_or_default() < required {
+                        waiting_since.insert(coder_id, at + length);
+                    }
+                }
+                EventKind::Burnout => timeline.burnout = Some((coder_id, at)),
+            }
+        }
+
+        // what was still going on when the run stopped
+        for (coder_id, since) in waiting_since {
+            if since < end {
+                timeline.slices.push(Slice {
+                    track: Track::Coder(coder_id),
+                    kind: SliceKind::Waiting,
+                    start: since,
+                    end,
+                });
+            }
+        }
+        for (dongle_id, (holder, since)) in held_since {
+            timeline.slices.push(Slice {
+                track: Track::Dongle(dongle_id),
+                kind: SliceKind::Held { coder_id: holder },
+                start: since,
+                end,
+            });
+        }
+
+        timeline
+    }
+
+    /// The run up to `end` in the Chrome Trace Event format: a track per
+    /// coder with his phases and waits, a track per dongle with its holds
+    /// and cooldowns, and an arrow from a release to the coder who waited for it
+    pub fn to_json(&self, end: Duration) -> String {
+        let timeline = self.timeline(end);
+        let mut trace = Vec::new();
+
+        trace.push(process_name(CODERS_PID, "coders"));
+        trace.push(process_name(DONGLES_PID, "dongles"));
+        for coder_id in 1..=timeline.number_of_coders {
+            trace.push(thread_name(
+                CODERS_PID,
+                coder_id,
+                &format!("coder {coder_id}"),
+            ));
+        }
+        for dongle_id in 1..=timeline.number_of_dongles {
+            trace.push(thread_name(
+                DONGLES_PID,
+                dongle_id,
+                &format!("dongle {dongle_id}"),
+            ));
+        }
+
+        trace.extend(timeline.slices.iter().map(complete));
+        for (idx, handover) in timeline.handovers.iter().enumerate() {
+            trace.push(flow_start(idx + 1, handover));
+            trace.push(flow_end(idx + 1, handover));
+        }
+        if let Some((coder_id, at)) = timeline.burnout {
+            trace.push(format!(
+                "{{\"name\":\"burnout\",\"cat\":\"coder\",\"ph\":\"i\",\"s\":\"g\",\"ts\":{},\"pid\":{CODERS_PID},\"tid\":{coder_id}}}",
+                at.as_micros()
+            ));
+        }
+
+        format!(
+            "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+            trace.join(",\n")
+        )
+    }
+
+    /// How long the phase of the event lasts, as drawn or as set
+    fn length(&self, event: &Event, phase: impl Fn(&CoderProfile) -> Distribution) -> Duration {
+        event.duration.unwrap_or_else(|| {
+            self.profiles
+                .get(event.coder_id as usize - 1)
+                .map_or(Duration::ZERO, |profile| phase(profile).mean())
+        })
+    }
+}
+
+impl Observer for Trace {
+    fn on_event(&self, event: &Event) {
+        self.events.lock().unwrap().push(*event);
+    }
+}
+
+fn process_name(pid: u32, name: &str) -> String {
+    format!(
+        "{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{pid},\"args\":{{\"name\":\"{name}\"}}}}"
+    )
+}
+
+fn thread_name(pid: u32, tid: u32, name: &str) -> String {
+    let mut metadata = format!(
+        "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{pid},\"tid\":{tid},\"args\":{{\"name\":\"{name}\"}}}}"
+    );
+    // keep the tracks in id order
+    let _ = write!(
+        metadata,
+        ",\n{{\"name\":\"thread_sort_index\",\"ph\":\"M\",\"pid\":{pid},\"tid\":{tid},\"args\":{{\"sort_index\":{tid}}}}}"
+    );
+    metadata
+}
+
+fn complete(slice: &Slice) -> String {
+    let (category, pid, tid) = match slice.track {
+        Track::Coder(coder_id) => ("coder", CODERS_PID, coder_id),
+        Track::Dongle(dongle_id) => ("dongle", DONGLES_PID, dongle_id),
+    };
+    format!(
+        "{{\"name\":\"{}\",\"cat\":\"{category}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":{pid},\"tid\":{tid}}}",
+        slice.kind.name(),
+        slice.start.as_micros(),
+        slice.end.saturating_sub(slice.start).as_micros()
+    )
+}
+
+/// Start of an arrow, on the cooldown of the released dongle
+fn flow_start(id: usize, handover: &Handover) -> String {
+    format!(
+        "{{\"name\":\"handed over\",\"cat\":\"wait\",\"ph\":\"s\",\"id\":{id},\"ts\":{},\"pid\":{DONGLES_PID},\"tid\":{}}}",
+        handover.released_at.as_micros(),
+        handover.dongle_id
+    )
+}
+
+/// End of an arrow, on the wait of the coder who gets the dongle
+fn flow_end(id: usize, handover: &Handover) -> String {
+    format!(
+        "{{\"name\":\"handed over\",\"cat\":\"wait\",\"ph\":\"f\",\"bp\":\"e\",\"id\":{id},\"ts\":{},\"pid\":{CODERS_PID},\"tid\":{}}}",
+        handover.acquired_at.as_micros(),
+        handover.to_coder
+    )
+}
@@ -0,0 +1,459 @@
+use std::{
+    collections::HashMap, error::Error, fmt::Display, fs, io, num::ParseIntError, time::Duration,
+};
+
+use crate::{
+    args::{Args, ArgsError},
+    json::{JsonError, Value},
+    logging::EventKind,
+    topology::Topology,
+};
+
+/// Rules a recorded run is checked against
+#[derive(Debug)]
+pub struct VerifyOptions {
+    pub path: String,
+    /// inferred from the ids found in the log when not given
+    pub number_of_coders: Option<u32>,
+    /// which dongles each coder may take
+    pub topology: Topology,
+    pub dongle_cooldown: Duration,
+    /// the burnout delay is only checked when this is given
+    pub time_to_burnout: Option<Duration>,
+    /// how late a burnout line may be printed after the deadline
+    pub burnout_tolerance: Duration,
+}
+
+/// One line of a JSON Lines log
+#[derive(Clone, Copy, Debug)]
+pub struct Record {
+    pub sequence: u64,
+    pub micros: u64,
+    pub coder_id: u32,
+    pub event: EventKind,
+    /// drawn length of the phase, or of the cooldown for a release, when
+    /// durations were random
+    pub duration_us: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct Violation {
+    pub sequence: u64,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum VerifyError {
+    MissingLogFile,
+    MissingValue(String),
+    InvalidNumber {
+        option: String,
+        source: ParseIntError,
+    },
+    /// a duration or the topology
+    InvalidArgument(ArgsError),
+    UnknownOption(String),
+    Io(io::Error),
+    InvalidJson {
+        line: usize,
+        source: JsonError,
+    },
+    InvalidRecord {
+        line: usize,
+    },
+}
+
+impl TryFrom<&[String]> for VerifyOptions {
+    type Error = VerifyError;
+
+    fn try_from(args: &[String]) -> Result<Self, Self::Error> {
+        let mut args = args.iter();
+        let path = args.next().ok_or(VerifyError::MissingLogFile)?.clone();
+
+        let mut options = Self {
+            path,
+            number_of_coders: None,
+            topology: Topology::Ring,
+            dongle_cooldown: Duration::ZERO,
+            time_to_burnout: None,
+            burnout_tolerance: Duration::from_millis(10),
+        };
+
+        while let Some(option) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| VerifyError::MissingValue(option.clone()))?;
+            let duration = |argument| Args::parse_duration(value, argument);
+
+            match option.as_str() {
+                "--coders" => {
+                    options.number_of_coders =
+                        Some(value.parse().map_err(|source| VerifyError::InvalidNumber {
+                            option: option.clone(),
+                            source,
+                        })?)
+                }
+                "--topology" => options.topology = value.as_str().try_into()?,
+                "--cooldown" => options.dongle_cooldown = duration("--cooldown")?,
+                "--burnout" => options.time_to_burnout = Some(duration("--burnout")?),
+                "--tolerance" => options.burnout_tolerance = duration("--tolerance")?,
+                _ => return Err(VerifyError::UnknownOption(option.clone())),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Check the log file and print every violation, returns whether the run is valid
+pub fn run(args: &[String]) -> Result<bool, VerifyError> {
+    let options = VerifyOptions::try_from(args)?;
+    let text = fs::read_to_string(&options.path).map_err(VerifyError::Io)?;
+    let records = parse_records(&text)?;
+
+    let violations = verify(&records, &options);
+    for violation in &violations {
+        println!("seq {}: {}", violation.sequence, violation.message);
+    }
+
+    if violations.is_empty() {
+        println!("OK: {} events, no violation", records.len());
+    } else {
+        println!(
+            "FAILED: {} violation(s) in {} events",
+            violations.len(),
+            records.len()
+        );
+    }
+    Ok(violations.is_empty())
+}
+
+pub fn parse_records(text: &str) -> Result<Vec<Record>, VerifyError> {
+    let mut records = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_number = idx + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value = Value::parse(line).map_err(|source| VerifyError::InvalidJson {
+            line: line_number,
+            source,
+        })?;
+        let record =
+            parse_record(&value).ok_or(VerifyError::InvalidRecord { line: line_number })?;
+        records.push(record);
+    }
+
+    records.sort_by_key(|record| record.sequence);
+    Ok(records)
+}
+
+fn parse_record(value: &Value) -> Option<Record> {
+    let dongle_id = value
+        .get("dongle")
+        .and_then(Value::as_u64)
+        .map(|id| id as u32);
+
+    Some(Record {
+        sequence: value.get("seq")?.as_u64()?,
+        micros: value.get("us")?.as_u64()?,
+        coder_id: value.get("coder")?.as_u64()? as u32,
+        event: EventKind::from_name(value.get("event")?.as_str()?, dongle_id)?,
+        duration_us: value.get("duration_us").and_then(Value::as_u64),
+    })
+}
+
+/// Replay the records in order and collect every broken rule
+pub fn verify(records: &[Record], options: &VerifyOptions) -> Vec<Violation> {
+    let number_of_coders = options.number_of_coders.unwrap_or_else(|| {
+        records
+            .iter()
+            .map(|record| match options.topology {
+                // in a ring, a coder who never logged still has a dongle that did
+                Topology::Ring => record.coder_id.max(record.event.dongle_id().unwrap_or(0)),
+                _ => record.coder_id,
+            })
+            .max()
+            .unwrap_or(0)
+    });
+    let graph = options.topology.graph(number_of_coders);
+    let adjacent_dongles = |coder_id: u32| -> Vec<u32> {
+        graph
+            .coders
+            .get((coder_id as usize).wrapping_sub(1))
+            .map(|dongles| dongles.iter().map(|&idx| idx as u32 + 1).collect())
+            .unwrap_or_default()
+    };
+    let cooldown = options.dongle_cooldown.as_micros() as u64;
+
+    let mut violations = Vec::new();
+    let mut holders: HashMap<u32, u32> = HashMap::new();
+    // when each dongle was released, and its cooldown
+    let mut last_release: HashMap<u32, (u64, u64)> = HashMap::new();
+    let mut last_compile_end: HashMap<u32, u64> = HashMap::new();
+    let mut burnout_sequence = None;
+
+    for record in records {
+        let mut violation = |message: String| {
+            violations.push(Violation {
+                sequence: record.sequence,
+                message,
+            })
+        };
+        let coder_id = record.coder_id;
+
+        if let Some(sequence) = burnout_sequence {
+            violation(format!(
+                "coder_{coder_id} logged '{}' after the burnout at seq {sequence}",
+                record.event.name()
+            ));
+        }
+
+        match record.event {
+            EventKind::Acquire { dongle_id } => {
+                if !adjacent_dongles(coder_id).contains(&dongle_id) {
+                    violation(format!(
+                        "coder_{coder_id} acquired dongle_{dongle_id} which he doesn't need"
+                    ));
+                }
+                if let Some(holder) = holders.get(&dongle_id) {
+                    violation(format!(
+                        "coder_{coder_id} acquired dongle_{dongle_id} while coder_{holder} holds it"
+                    ));
+                }
+                if let Some(&(released_at, cooldown)) = last_release.get(&dongle_id)
+                    && record.micros < released_at + cooldown
+                {
+                    violation(format!(
+                        "coder_{coder_id} acquired dongle_{dongle_id} {}us after its release, cooldown is {cooldown}us",
+                        record.micros - released_at
+                    ));
+                }
+                holders.insert(dongle_id, coder_id);
+            }
+            EventKind::Release { dongle_id } => {
+                if holders.get(&dongle_id) != Some(&coder_id) {
+                    violation(format!(
+                        "coder_{coder_id} released dongle_{dongle_id} without holding it"
+                    ));
+                }
+                holders.remove(&dongle_id);
+                // a drawn cooldown was logged with the release
+                let cooldown = record.duration_us.unwrap_or(cooldown);
+                last_release.insert(dongle_id, (record.micros, cooldown));
+                last_compile_end.insert(coder_id, record.micros);
+            }
+            EventKind::Compile => {
+                for dongle_id in adjacent_dongles(coder_id) {
+                    if holders.get(&dongle_id) != Some(&coder_id) {
+                        violation(format!(
+                            "coder_{coder_id} compiled without holding dongle_{dongle_id}"
+                        ));
+                    }
+                }
+            }
+            EventKind::Debug | EventKind::Refactor => (),
+            EventKind::Burnout => {
+                if let Some(time_to_burnout) = options.time_to_burnout {
+                    let deadline = last_compile_end.get(&coder_id).copied().unwrap_or(0)
+                        + time_to_burnout.as_micros() as u64;
+                    let tolerance = options.burnout_tolerance.as_micros() as u64;
+
+                    if record.micros < deadline {
+                        violation(format!(
+                            "coder_{coder_id} burned out {}us before his deadline",
+                            deadline - record.micros
+                        ));
+                    } else if record.micros > deadline + tolerance {
+                        violation(format!(
+                            "coder_{coder_id} burnout logged {}us after his deadline, tolerance is {tolerance}us",
+                            record.micros - deadline
+                        ));
+                    }
+                }
+                burnout_sequence.get_or_insert(record.sequence);
+            }
+        }
+    }
+
+    violations
+}
+
+impl VerifyError {
+    /// Whether the command line is wrong, rather than the log file
+    pub fn is_usage_error(&self) -> bool {
+        match self {
+            VerifyError::MissingLogFile
+            | VerifyError::MissingValue(_)
+            | VerifyError::InvalidNumber { .. }
+            | VerifyError::InvalidArgument(_)
+            | VerifyError::UnknownOption(_) => true,
+            VerifyError::Io(_)
+            | VerifyError::InvalidJson { .. }
+            | VerifyError::InvalidRecord { .. } => false,
+        }
+    }
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::MissingLogFile => write!(f, "Error: missing log file"),
+            VerifyError::MissingValue(option) => {
+                write!(f, "Error: missing value for option '{option}'")
+            }
+            VerifyError::InvalidNumber { option, source } => {
+                write!(f, "Error: invalid number for option '{option}': {source}")
+            }
+            VerifyError::InvalidArgument(source) => write!(f, "{source}"),
+            VerifyError::UnknownOption(option) => write!(f, "Error: unknown option '{option}'"),
+            VerifyError::Io(source) => write!(f, "Error: can't read log file: {source}"),
+            VerifyError::InvalidJson { line, source } => {
+                write!(f, "Error: invalid JSON on line {line}: {source}")
+            }
+            VerifyError::InvalidRecord { line } => {
+                write!(f, "Error: line {line} is not a codexion event")
+            }
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+impl From<ArgsError> for VerifyError {
+    fn from(source: ArgsError) -> Self {
+        VerifyError::InvalidArgument(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three coders in a ring: coder 1 needs dongles 1 and 2, coder 2 needs 2
+    /// and 3, coder 3 needs 3 and 1
+    fn options() -> VerifyOptions {
+        VerifyOptions {
+            path: "run.jsonl".to_string(),
+            number_of_coders: Some(3),
+            topology: Topology::Ring,
+            dongle_cooldown: Duration::from_micros(100),
+            time_to_burnout: Some(Duration::from_secs(1)),
+            burnout_tolerance: Duration::from_millis(10),
+        }
+    }
+
+    /// Messages of every violation in the log, one event per line as
+    /// `us coder event [dongle]`
+    fn violations(log: &str) -> Vec<String> {
+        let lines: String = log
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(seq, line)| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let dongle = fields.get(3).copied().unwrap_or("null");
+                format!(
+                    "{{\"seq\":{seq},\"us\":{},\"coder\":{},\"event\":\"{}\",\"dongle\":{dongle}}}\n",
+                    fields[0], fields[1], fields[2]
+                )
+            })
+            .collect();
+        let records = parse_records(&lines).unwrap();
+        verify(&records, &options())
+            .into_iter()
+            .map(|violation| violation.message)
+            .collect()
+    }
+
+    const FIRST_COMPILE: &str = "
+        0 1 acquire 1
+        0 1 acquire 2
+        0 1 compile
+        200000 1 release 2
+        200000 1 release 1
+        200000 1 debug
+    ";
+
+    #[test]
+    fn clean_log_has_no_violation() {
+        let log = format!(
+            "{FIRST_COMPILE}
+            200100 2 acquire 2
+            200100 2 acquire 3
+            200100 2 compile
+            400000 1 refactor
+            400100 2 release 3
+            400100 2 release 2
+            400100 2 debug
+            400200 3 acquire 3
+            400200 3 acquire 1
+            400200 3 compile
+            600200 3 release 1
+            600200 3 release 3"
+        );
+        assert_eq!(violations(&log), Vec::<String>::new());
+    }
+
+    #[test]
+    fn a_dongle_is_held_by_one_coder_at_a_time() {
+        let log = "
+            0 1 acquire 2
+            10 2 acquire 2
+        ";
+        assert_eq!(
+            violations(log),
+            ["coder_2 acquired dongle_2 while coder_1 holds it"]
+        );
+    }
+
+    #[test]
+    fn a_dongle_cools_down_after_a_release() {
+        let log = format!("{FIRST_COMPILE}\n200050 2 acquire 2");
+        assert_eq!(
+            violations(&log),
+            ["coder_2 acquired dongle_2 50us after its release, cooldown is 100us"]
+        );
+    }
+
+    #[test]
+    fn a_coder_compiles_with_both_of_his_dongles() {
+        let log = "
+            0 1 acquire 1
+            0 1 compile
+        ";
+        assert_eq!(
+            violations(log),
+            ["coder_1 compiled without holding dongle_2"]
+        );
+    }
+
+    #[test]
+    fn nothing_happens_after_a_burnout() {
+        let log = format!("{FIRST_COMPILE}\n1000000 3 burnout\n1000100 1 refactor");
+        assert_eq!(
+            violations(&log),
+            ["coder_1 logged 'refactor' after the burnout at seq 6"]
+        );
+    }
+
+    #[test]
+    fn a_burnout_comes_on_time() {
+        // coder 1's deadline is 1s after his release at 200ms
+        let early = format!("{FIRST_COMPILE}\n900000 1 burnout");
+        assert_eq!(
+            violations(&early),
+            ["coder_1 burned out 300000us before his deadline"]
+        );
+        let late = format!("{FIRST_COMPILE}\n1250000 1 burnout");
+        assert_eq!(
+            violations(&late),
+            ["coder_1 burnout logged 50000us after his deadline, tolerance is 10000us"]
+        );
+        let on_time = format!("{FIRST_COMPILE}\n1205000 1 burnout");
+        assert_eq!(violations(&on_time), Vec::<String>::new());
+    }
+}
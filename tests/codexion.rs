@@ -0,0 +1,306 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use codexion_rs::{
+    Args, ArgsBuilder, Backend, ClockMode, CoderOverride, Codexion, Distribution, Event, EventKind,
+    Fifo, LogFormat, Observer, SchedulerKind, SimulationOutcome, Topology, replay::Recording,
+};
+
+/// Keeps every event of a run
+#[derive(Default)]
+struct Events(Mutex<Vec<Event>>);
+
+impl Observer for Events {
+    fn on_event(&self, event: &Event) {
+        self.0.lock().unwrap().push(*event);
+    }
+}
+
+fn virtual_args() -> ArgsBuilder {
+    Args::builder()
+        .clock(ClockMode::Virtual)
+        .log_format(LogFormat::Quiet)
+}
+
+/// Run on the virtual clock, with its outcome and every event
+fn run(codexion: Codexion) -> (SimulationOutcome, Vec<Event>, Codexion) {
+    let events = Arc::new(Events::default());
+    codexion.observe(Arc::clone(&events) as Arc<dyn Observer>);
+    let outcome = codexion.start();
+    let events = events.0.lock().unwrap().clone();
+    (outcome, events, codexion)
+}
+
+fn compiles_of(events: &[Event], coder_id: u32) -> usize {
+    events
+        .iter()
+        .filter(|event| event.coder_id == coder_id && event.kind == EventKind::Compile)
+        .count()
+}
+
+#[test]
+fn every_coder_does_his_compiles() {
+    let args = virtual_args()
+        .number_of_coders(5)
+        .number_of_compiles_required(3)
+        .build()
+        .unwrap();
+
+    let (outcome, events, codexion) = run(Codexion::new(args));
+
+    assert_eq!(outcome, SimulationOutcome::Completed);
+    for coder_id in 1..=5 {
+        assert_eq!(compiles_of(&events, coder_id), 3);
+    }
+    assert_eq!(codexion.statistics().total_compiles(), 15);
+    assert!(
+        events
+            .windows(2)
+            .all(|pair| pair[0].elapsed <= pair[1].elapsed)
+    );
+}
+
+#[test]
+fn a_coder_who_waits_too_long_burns_out() {
+    let args = virtual_args()
+        .number_of_coders(3)
+        .time_to_burnout(Duration::from_millis(300))
+        .build()
+        .unwrap();
+
+    let (outcome, events, _) = run(Codexion::new(args));
+
+    let SimulationOutcome::BurnedOut { coder_id, elapsed } = outcome else {
+        panic!("expected a burnout, got {outcome:?}");
+    };
+    let last = events.last().unwrap();
+    assert_eq!(last.kind, EventKind::Burnout);
+    assert_eq!(last.coder_id, coder_id);
+    assert_eq!(last.elapsed, elapsed);
+}
+
+#[test]
+fn the_same_seed_gives_the_same_run() {
+    let args = virtual_args()
+        .number_of_coders(4)
+        .time_to_compile(Distribution::Uniform {
+            min: Duration::from_millis(50),
+            max: Duration::from_millis(250),
+        })
+        .dongle_cooldown(Distribution::Exponential {
+            mean: Duration::from_millis(20),
+        })
+        .seed(42)
+        .build()
+        .unwrap();
+
+    let (first_outcome, first, _) = run(Codexion::new(args.clone()));
+    let (second_outcome, second, _) = run(Codexion::new(args));
+
+    assert_eq!(first_outcome, second_outcome);
+    assert_eq!(first, second);
+    assert!(first.iter().all(|event| match event.kind {
+        EventKind::Compile | EventKind::Release { .. } => event.duration.is_some(),
+        _ => true,
+    }));
+}
+
+#[test]
+fn dongles_can_use_any_scheduler() {
+    let args = virtual_args().number_of_coders(4).build().unwrap();
+
+    let codexion = Codexion::with_scheduler(args, |_| Box::new(Fifo::default()));
+    let (outcome, events, _) = run(codexion);
+
+    assert_eq!(outcome, SimulationOutcome::Completed);
+    assert_eq!(compiles_of(&events, 1), 3);
+}
+
+/// Which coder got each dongle, in order
+fn grants(events: &[Event]) -> Vec<(u32, u32)> {
+    events
+        .iter()
+        .filter_map(|event| match event.kind {
+            EventKind::Acquire { dongle_id } => Some((dongle_id, event.coder_id)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn the_lottery_is_not_first_come_first_served() {
+    // the cooldowns outlast debugging and refactoring, so both sharers of a
+    // ring's dongle are back in line when it's free
+    let args = |scheduler| {
+        virtual_args()
+            .number_of_coders(4)
+            .number_of_compiles_required(10)
+            .time_to_burnout(Duration::from_secs(10))
+            .time_to_debug(Duration::from_millis(5))
+            .time_to_refactor(Duration::from_millis(5))
+            .dongle_cooldown(Duration::from_millis(100))
+            .scheduler(scheduler)
+            .seed(7)
+            .build()
+            .unwrap()
+    };
+
+    let (fifo_outcome, fifo, _) = run(Codexion::new(args(SchedulerKind::Fifo)));
+    let (lottery_outcome, lottery, _) =
+        run(Codexion::new(args(SchedulerKind::Lottery { seed: None })));
+    let (_, lottery_again, _) = run(Codexion::new(args(SchedulerKind::Lottery { seed: None })));
+
+    assert_eq!(fifo_outcome, SimulationOutcome::Completed);
+    assert_eq!(lottery_outcome, SimulationOutcome::Completed);
+    assert_ne!(grants(&fifo), grants(&lottery));
+    assert_eq!(grants(&lottery), grants(&lottery_again));
+}
+
+/// Panics if a dongle is acquired while someone else holds it, or released
+/// by someone who doesn't
+fn assert_exclusive(events: &[Event]) {
+    let mut holders = BTreeMap::new();
+    for event in events {
+        match event.kind {
+            EventKind::Acquire { dongle_id } => {
+                if let Some(holder) = holders.insert(dongle_id, event.coder_id) {
+                    panic!(
+                        "coder {} took dongle {dongle_id} from coder {holder}",
+                        event.coder_id
+                    );
+                }
+            }
+            EventKind::Release { dongle_id } => {
+                assert_eq!(holders.remove(&dongle_id), Some(event.coder_id));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Run the backend on `topology` until every coder did his 4 compiles
+fn run_backend(args: ArgsBuilder, topology: Topology, number_of_coders: u32) {
+    let args = args
+        .topology(topology)
+        .number_of_coders(number_of_coders)
+        .number_of_compiles_required(4)
+        .build()
+        .unwrap();
+
+    let (outcome, events, _) = run(Codexion::new(args));
+
+    assert_eq!(outcome, SimulationOutcome::Completed, "{topology:?}");
+    assert_exclusive(&events);
+    for coder_id in 1..=number_of_coders {
+        assert_eq!(compiles_of(&events, coder_id), 4, "{topology:?}");
+    }
+}
+
+#[test]
+fn the_arbiter_runs_every_topology() {
+    let arbiter = || {
+        virtual_args()
+            .backend(Backend::Arbiter)
+            .time_to_burnout(Duration::from_secs(10))
+    };
+
+    run_backend(arbiter(), Topology::Ring, 5);
+    run_backend(arbiter(), Topology::Star, 5);
+    run_backend(arbiter(), Topology::Complete, 5);
+}
+
+#[test]
+fn chandy_misra_runs_every_topology() {
+    // it only runs on threads, and shares a dongle between two coders at most
+    let chandy_misra = || {
+        Args::builder()
+            .backend(Backend::ChandyMisra)
+            .log_format(LogFormat::Quiet)
+            .time_to_burnout(Duration::from_secs(10))
+            .time_to_compile(Duration::from_millis(4))
+            .time_to_debug(Duration::from_millis(2))
+            .time_to_refactor(Duration::from_millis(2))
+            .dongle_cooldown(Duration::from_millis(1))
+    };
+
+    run_backend(chandy_misra(), Topology::Ring, 5);
+    run_backend(chandy_misra(), Topology::Star, 2);
+    run_backend(chandy_misra(), Topology::Complete, 2);
+}
+
+/// A run with random durations, where coders are back in line before the
+/// cooldowns end
+fn recorded_args(scheduler: SchedulerKind, seed: u64) -> ArgsBuilder {
+    virtual_args()
+        .number_of_coders(5)
+        .time_to_burnout(Duration::from_secs(10))
+        .time_to_compile(Distribution::Uniform {
+            min: Duration::from_millis(50),
+            max: Duration::from_millis(250),
+        })
+        .time_to_debug(Duration::from_millis(5))
+        .time_to_refactor(Duration::from_millis(5))
+        .dongle_cooldown(Duration::from_millis(100))
+        .scheduler(scheduler)
+        .seed(seed)
+}
+
+fn record(args: ArgsBuilder, name: &str) -> Recording {
+    let path = std::env::temp_dir().join(format!("codexion-{}-{name}.jsonl", std::process::id()));
+    let path = path.to_str().unwrap();
+    let (outcome, _, _) = run(Codexion::new(args.record(path).build().unwrap()));
+    assert_eq!(outcome, SimulationOutcome::Completed);
+
+    let recording = Recording::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    recording
+}
+
+#[test]
+fn a_replay_grants_the_dongles_in_the_recorded_order() {
+    let lottery = SchedulerKind::Lottery { seed: None };
+    let recording = record(recorded_args(lottery, 3), "round-trip");
+    let (_, recorded, _) = run(Codexion::new(recorded_args(lottery, 3).build().unwrap()));
+    let (_, fifo, _) = run(Codexion::new(
+        recorded_args(SchedulerKind::Fifo, 3).build().unwrap(),
+    ));
+    assert_ne!(grants(&recorded), grants(&fifo));
+
+    let replay = recorded_args(SchedulerKind::Fifo, 3)
+        .replay(recording)
+        .build()
+        .unwrap();
+    let (outcome, replayed, _) = run(Codexion::new(replay));
+
+    assert_eq!(outcome, SimulationOutcome::Completed);
+    assert_eq!(grants(&replayed), grants(&recorded));
+}
+
+#[test]
+fn a_replay_goes_on_when_a_recorded_coder_never_comes() {
+    let recording = record(recorded_args(SchedulerKind::Edf, 3), "strays");
+
+    // coder 1 leaves after one compile, his next grants are never taken
+    let replay = recorded_args(SchedulerKind::Edf, 3)
+        .coder_override(
+            1,
+            CoderOverride {
+                number_of_compiles_required: Some(1),
+                ..Default::default()
+            },
+        )
+        .replay(recording)
+        .build()
+        .unwrap();
+    let (outcome, events, _) = run(Codexion::new(replay));
+
+    assert_eq!(outcome, SimulationOutcome::Completed);
+    assert_exclusive(&events);
+    assert_eq!(compiles_of(&events, 1), 1);
+    for coder_id in 2..=5 {
+        assert_eq!(compiles_of(&events, coder_id), 3);
+    }
+}